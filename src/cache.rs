@@ -0,0 +1,309 @@
+//! An on-disk cache of the imports parsed from each file, so only changed files are reparsed.
+//!
+//! A project's cache is a JSON file in the cache directory, named after a hash of the project's
+//! path. Each file is looked up by its path: if its size and modification time are unchanged, its
+//! cached imports are used as they are. Otherwise its contents are hashed, and it is only parsed
+//! if they changed. The whole cache is discarded when the parser changes.
+
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+    time::UNIX_EPOCH,
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::encoding::decode;
+use crate::parser::{self, ImportStatement, parse_python_file};
+
+/// The imports of a file, and a warning if it could not be decoded as it declares.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Parsed {
+    pub imports: Vec<ImportStatement>,
+    pub warning: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    size: u64,
+    /// The modification time, in nanoseconds since the Unix epoch.
+    mtime: u64,
+    /// The FNV-1a hash of the contents.
+    hash: u64,
+    parsed: Parsed,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    version: String,
+    entries: HashMap<PathBuf, Entry>,
+}
+
+/// The imports parsed from each file of a project, by path.
+#[derive(Debug, Default)]
+pub struct Cache {
+    /// Where the cache is saved, or `None` if caching is disabled.
+    path: Option<PathBuf>,
+    /// The entries read from disk.
+    old: HashMap<PathBuf, Entry>,
+    /// The entries of the files parsed or looked up this run.
+    new: Mutex<HashMap<PathBuf, Entry>>,
+    /// The contents of files with unsaved changes, which are parsed instead of the files, and
+    /// never cached.
+    unsaved: HashMap<PathBuf, String>,
+}
+
+/// The version of the cache format and parser, which must match for a cache to be used.
+fn version() -> String {
+    format!("{}+parser.{}", env!("CARGO_PKG_VERSION"), parser::VERSION)
+}
+
+/// The FNV-1a hash of `bytes`, which is stable across platforms and releases.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// The directory caches are kept in by default: `$XDG_CACHE_HOME/poetry-udeps`, or the
+/// platform's usual cache directory.
+pub fn default_dir() -> Option<PathBuf> {
+    let var = |name: &str| {
+        env::var_os(name)
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+    };
+    let base = var("XDG_CACHE_HOME")
+        .or_else(|| var("LOCALAPPDATA"))
+        .or_else(|| var("HOME").map(|home| home.join(".cache")))?;
+    Some(base.join(env!("CARGO_PKG_NAME")))
+}
+
+impl Cache {
+    /// A cache which parses every file, and saves nothing.
+    pub fn disabled() -> Cache {
+        Cache::default()
+    }
+
+    /// Parse the files in `unsaved` from the contents given, rather than from disk.
+    pub fn with_unsaved(mut self, unsaved: HashMap<PathBuf, String>) -> Cache {
+        self.unsaved = unsaved;
+        self
+    }
+
+    /// Open the cache of the project in `root`, kept in `dir`.
+    ///
+    /// A cache which is missing, unreadable or from another version is started afresh.
+    pub fn open(dir: &Path, root: &Path) -> Cache {
+        let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+        let path = dir.join(format!(
+            "{:016x}.json",
+            fnv1a(root.as_os_str().as_encoded_bytes())
+        ));
+        let old = match Cache::read(&path) {
+            Ok(Some(file)) if file.version == version() => file.entries,
+            Ok(Some(file)) => {
+                info!(
+                    version = file.version,
+                    "Discarding the cache of another version"
+                );
+                HashMap::new()
+            }
+            Ok(None) => HashMap::new(),
+            Err(e) => {
+                warn!("Discarding the cache: {e:#}");
+                HashMap::new()
+            }
+        };
+        debug!(path = %path.display(), entries = old.len(), "Opened cache");
+        Cache {
+            path: Some(path),
+            old,
+            ..Cache::default()
+        }
+    }
+
+    fn read(path: &Path) -> Result<Option<CacheFile>> {
+        let file = match fs::File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
+        };
+        let file = serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("failed to parse {}", path.display()))?;
+        Ok(Some(file))
+    }
+
+    /// Parse the imports of the file at `path`, unless it is unchanged since it was cached.
+    ///
+    /// Returns whether the cached imports were used.
+    pub fn parse(&self, path: &Path) -> Result<(Parsed, bool)> {
+        if let Some(contents) = self.unsaved.get(path) {
+            return Ok((parse(contents.as_bytes())?, false));
+        }
+        if self.path.is_none() {
+            return Ok((parse(&fs::read(path).context("unable to read")?)?, false));
+        }
+        let metadata = fs::metadata(path).context("unable to read")?;
+        let size = metadata.len();
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .and_then(|time| u64::try_from(time.as_nanos()).ok())
+            .unwrap_or_default();
+        let cached = self.old.get(path);
+        if let Some(entry) = cached
+            && entry.size == size
+            && entry.mtime == mtime
+        {
+            return Ok((self.keep(path, entry.clone()), true));
+        }
+
+        let buf = fs::read(path).context("unable to read")?;
+        let hash = fnv1a(&buf);
+        let (parsed, hit) = match cached {
+            Some(entry) if entry.hash == hash => (entry.parsed.clone(), true),
+            _ => (parse(&buf)?, false),
+        };
+        let entry = Entry {
+            size,
+            mtime,
+            hash,
+            parsed,
+        };
+        Ok((self.keep(path, entry), hit))
+    }
+
+    /// Keep the entry for `path`, to be saved.
+    fn keep(&self, path: &Path, entry: Entry) -> Parsed {
+        let parsed = entry.parsed.clone();
+        self.new
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(path.to_path_buf(), entry);
+        parsed
+    }
+
+    /// Save the entries of the files seen this run, and of the other files which still exist.
+    pub fn save(self) -> Result<()> {
+        let Some(path) = self.path else {
+            return Ok(());
+        };
+        let mut entries = self
+            .new
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+        for (file, entry) in self.old {
+            if !entries.contains_key(&file) && file.is_file() {
+                entries.insert(file, entry);
+            }
+        }
+        let dir = path.parent().unwrap_or(Path::new("."));
+        fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+        // Write to a temporary file first, so that concurrent runs never read half a cache
+        let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+        let file = CacheFile {
+            version: version(),
+            entries,
+        };
+        let write = || -> Result<()> {
+            let mut writer = BufWriter::new(fs::File::create(&tmp)?);
+            serde_json::to_writer(&mut writer, &file)?;
+            writer.flush()?;
+            fs::rename(&tmp, &path)?;
+            Ok(())
+        };
+        write().with_context(|| format!("failed to write {}", path.display()))?;
+        debug!(path = %path.display(), entries = file.entries.len(), "Saved cache");
+        Ok(())
+    }
+}
+
+/// Decode and parse the contents of a Python file.
+fn parse(buf: &[u8]) -> Result<Parsed> {
+    let (contents, warning) = decode(buf);
+    let imports = parse_python_file(&contents).context("unable to parse")?;
+    Ok(Parsed { imports, warning })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_cache() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("cache");
+        let file = root.path().join("app.py");
+        fs::write(&file, "import requests\n").unwrap();
+
+        let cache = Cache::open(&dir, root.path());
+        let (parsed, hit) = cache.parse(&file).unwrap();
+        assert_eq!(parsed.imports[0].package, "requests");
+        assert!(!hit);
+        cache.save().unwrap();
+
+        let cache = Cache::open(&dir, root.path());
+        assert_eq!(cache.parse(&file).unwrap(), (parsed, true));
+        cache.save().unwrap();
+
+        // Only the contents matter once the size or modification time changed
+        fs::write(&file, "import yaml\n\n\n").unwrap();
+        let cache = Cache::open(&dir, root.path());
+        let (parsed, hit) = cache.parse(&file).unwrap();
+        assert_eq!(parsed.imports[0].package, "yaml");
+        assert!(!hit);
+        cache.save().unwrap();
+
+        // Deleted files are forgotten
+        fs::remove_file(&file).unwrap();
+        let cache = Cache::open(&dir, root.path());
+        assert!(cache.parse(&file).is_err());
+        cache.save().unwrap();
+        assert!(Cache::open(&dir, root.path()).old.is_empty());
+    }
+
+    #[test]
+    fn test_unsaved() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("cache");
+        let file = root.path().join("app.py");
+        fs::write(&file, "import requests\n").unwrap();
+
+        let unsaved = HashMap::from([(file.clone(), "import yaml\n".to_string())]);
+        let cache = Cache::open(&dir, root.path()).with_unsaved(unsaved);
+        let (parsed, hit) = cache.parse(&file).unwrap();
+        assert_eq!(parsed.imports[0].package, "yaml");
+        assert!(!hit);
+        cache.save().unwrap();
+
+        // Unsaved contents are never cached
+        let cache = Cache::open(&dir, root.path());
+        let (parsed, hit) = cache.parse(&file).unwrap();
+        assert_eq!(parsed.imports[0].package, "requests");
+        assert!(!hit);
+    }
+
+    #[test]
+    fn test_cache_version() {
+        let root = tempfile::tempdir().unwrap();
+        let file = root.path().join("app.py");
+        fs::write(&file, "import requests\n").unwrap();
+        let cache = Cache::open(root.path(), root.path());
+        cache.parse(&file).unwrap();
+        let path = cache.path.clone().unwrap();
+        cache.save().unwrap();
+        assert_eq!(Cache::open(root.path(), root.path()).old.len(), 1);
+
+        let json = fs::read_to_string(&path).unwrap();
+        fs::write(&path, json.replace(&version(), "0.0.0+parser.0")).unwrap();
+        assert!(Cache::open(root.path(), root.path()).old.is_empty());
+    }
+}
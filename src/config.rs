@@ -0,0 +1,74 @@
+//! Settings read from the `[tool.poetry-udeps]` table of pyproject.toml.
+
+use std::{fs, io, path::Path};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use toml::Value;
+
+use crate::manifest::PYPROJECT;
+
+/// The settings of a project, which command line options add to or override.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// Globs matching the project's package code, the only code which can credit main
+    /// dependencies.
+    pub include: Vec<String>,
+    /// Globs matching files and directories which are not scanned at all.
+    pub exclude: Vec<String>,
+}
+
+impl Config {
+    /// Read the settings of the project in `root`, which are empty without a pyproject.toml,
+    /// taking it to hold its `unsaved` contents, if any.
+    pub fn read(root: &Path, unsaved: Option<&str>) -> Result<Config> {
+        let path = root.join(PYPROJECT);
+        let toml = match unsaved.map_or_else(|| fs::read_to_string(&path), |toml| Ok(toml.into())) {
+            Ok(toml) => toml,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
+        };
+        let pyproject: Value = toml
+            .parse()
+            .with_context(|| format!("failed to parse {}", path.display()))?;
+        Config::from_pyproject(&pyproject)
+            .with_context(|| format!("invalid [tool.poetry-udeps] in {}", path.display()))
+    }
+
+    fn from_pyproject(pyproject: &Value) -> Result<Config> {
+        let Some(table) = pyproject
+            .get("tool")
+            .and_then(|tool| tool.get("poetry-udeps"))
+        else {
+            return Ok(Config::default());
+        };
+        Ok(table.clone().try_into()?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_from_pyproject() {
+        let config = |toml: &str| Config::from_pyproject(&toml.parse().unwrap());
+        assert_eq!(config("[tool.poetry]\n").unwrap(), Config::default());
+        assert_eq!(
+            config(
+                r#"[tool.poetry-udeps]
+include = ["src/app"]
+exclude = ["migrations", "scripts/"]
+"#
+            )
+            .unwrap(),
+            Config {
+                include: vec!["src/app".to_string()],
+                exclude: vec!["migrations".to_string(), "scripts/".to_string()],
+            }
+        );
+        assert!(config("[tool.poetry-udeps]\nincludes = []\n").is_err());
+    }
+}
@@ -0,0 +1,136 @@
+//! Decoding Python source files, following PEP 263.
+//!
+//! Source files are UTF-8 unless a `coding` cookie in one of their first two lines says otherwise.
+//! Only the imports matter to us, and those are ASCII, so every 8-bit encoding is decoded as
+//! Latin-1, which maps each byte to a single character.
+
+use std::borrow::Cow;
+
+/// The encodings (after normalization) which are UTF-8.
+const UTF_8: &[&str] = &["utf-8", "utf8", "utf-8-sig", "u8", "utf", "cp65001"];
+
+/// Decode the contents of a Python source file.
+///
+/// Returns the text, and a warning if the file could not be decoded as it declares, in which
+/// case any invalid bytes are replaced.
+pub fn decode(bytes: &[u8]) -> (Cow<'_, str>, Option<String>) {
+    let bytes = bytes.strip_prefix(b"\xef\xbb\xbf").unwrap_or(bytes);
+    let encoding = coding_cookie(bytes).unwrap_or_else(|| "utf-8".to_string());
+    if UTF_8.contains(&encoding.as_str()) {
+        return match std::str::from_utf8(bytes) {
+            Ok(text) => (Cow::Borrowed(text), None),
+            Err(e) => (
+                String::from_utf8_lossy(bytes),
+                Some(format!(
+                    "invalid UTF-8 at byte {}, so it was decoded lossily",
+                    e.valid_up_to()
+                )),
+            ),
+        };
+    }
+    if is_8_bit(&encoding) {
+        return (
+            Cow::Owned(bytes.iter().copied().map(char::from).collect()),
+            None,
+        );
+    }
+    (
+        String::from_utf8_lossy(bytes),
+        Some(format!(
+            "unsupported encoding `{encoding}`, so it was decoded lossily as UTF-8"
+        )),
+    )
+}
+
+/// Find the encoding declared by a `# -*- coding: <encoding> -*-` comment in the first two
+/// lines, normalized to lowercase with `-` separators.
+fn coding_cookie(bytes: &[u8]) -> Option<String> {
+    for line in bytes.split(|b| *b == b'\n').take(2) {
+        let line = String::from_utf8_lossy(line);
+        let trimmed = line.trim_start_matches([' ', '\t', '\x0c']);
+        let Some(comment) = trimmed.strip_prefix('#') else {
+            // The cookie may only follow a comment line, e.g. a shebang
+            if trimmed.trim().is_empty() {
+                continue;
+            }
+            return None;
+        };
+        let Some((_, rest)) = comment.split_once("coding") else {
+            continue;
+        };
+        let Some(rest) = rest.strip_prefix([':', '=']) else {
+            continue;
+        };
+        let name: String = rest
+            .trim_start_matches([' ', '\t'])
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+            .collect();
+        if !name.is_empty() {
+            return Some(name.to_ascii_lowercase().replace('_', "-"));
+        }
+    }
+    None
+}
+
+/// Whether an encoding maps each byte to a single character, and is ASCII compatible.
+fn is_8_bit(encoding: &str) -> bool {
+    matches!(
+        encoding,
+        "latin-1" | "latin1" | "l1" | "ascii" | "us-ascii" | "646" | "iso8859" | "iso-8859"
+    ) || [
+        "iso-8859-",
+        "iso8859-",
+        "cp12",
+        "windows-12",
+        "latin-",
+        "koi8-",
+        "cp8",
+    ]
+    .iter()
+    .any(|prefix| encoding.starts_with(prefix))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_coding_cookie() {
+        assert_eq!(
+            coding_cookie(b"#!/usr/bin/env python\n# -*- coding: Latin_1 -*-\n").as_deref(),
+            Some("latin-1")
+        );
+        assert_eq!(
+            coding_cookie(b"# vim: set fileencoding=iso-8859-15 :\n").as_deref(),
+            Some("iso-8859-15")
+        );
+        assert_eq!(coding_cookie(b"import os\n# coding: latin-1\n"), None);
+        assert_eq!(coding_cookie(b"\n\n# coding: latin-1\n"), None);
+    }
+
+    #[test]
+    fn test_decode() {
+        let (text, warning) = decode(b"# coding: latin-1\nimport caf\xe9\n");
+        assert_eq!(text, "# coding: latin-1\nimport café\n");
+        assert_eq!(warning, None);
+
+        let (text, warning) = decode(b"\xef\xbb\xbfimport os\n");
+        assert_eq!(text, "import os\n");
+        assert_eq!(warning, None);
+
+        let (text, warning) = decode(b"import os # caf\xe9\n");
+        assert_eq!(text, "import os # caf\u{fffd}\n");
+        assert_eq!(
+            warning.as_deref(),
+            Some("invalid UTF-8 at byte 15, so it was decoded lossily")
+        );
+
+        let (_, warning) = decode(b"# coding: shift_jis\nimport os\n");
+        assert_eq!(
+            warning.as_deref(),
+            Some("unsupported encoding `shift-jis`, so it was decoded lossily as UTF-8")
+        );
+    }
+}
@@ -0,0 +1,491 @@
+//! Removing unused dependencies from pyproject.toml.
+//!
+//! Edits are made with `toml_edit`, so the formatting and comments of everything else in the file
+//! are kept.
+
+use std::{
+    collections::BTreeSet,
+    fs,
+    io::{self, BufRead, IsTerminal, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use similar::TextDiff;
+use toml_edit::{Array, DocumentMut, Item, RawString, Value};
+use tracing::info;
+use xshell::{Shell, cmd};
+
+use crate::{
+    manifest::{DEV_ARRAYS, DEV_TABLES, MAIN_ARRAYS, MAIN_TABLES, Requirement},
+    name::NormalizedName,
+};
+
+/// The arrays listing the dependencies of each extra. `*` matches any extra.
+const EXTRAS: &[&str] = &["tool.poetry.extras.*", "project.optional-dependencies.*"];
+
+/// A dependency removed from pyproject.toml.
+#[derive(Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Removal {
+    /// The dependency, as spelled in pyproject.toml.
+    pub package: String,
+    /// The dotted path of the table or array it was removed from.
+    pub location: String,
+}
+
+/// The result of removing unused dependencies from pyproject.toml.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Fix {
+    pub path: PathBuf,
+    pub original: String,
+    pub fixed: String,
+    pub removed: Vec<Removal>,
+    /// Unused dependencies which were not found in pyproject.toml, e.g. as they are declared in
+    /// a requirements file, and must be removed by hand.
+    pub missing: Vec<String>,
+}
+
+impl Fix {
+    /// A unified diff of the changes to pyproject.toml.
+    #[must_use]
+    pub fn diff(&self) -> String {
+        let name = self.path.display().to_string();
+        TextDiff::from_lines(&self.original, &self.fixed)
+            .unified_diff()
+            .header(&format!("a/{name}"), &format!("b/{name}"))
+            .to_string()
+    }
+}
+
+/// Remove `udeps` from the pyproject.toml at `path`, writing the result unless `dry_run` is set.
+///
+/// Dependencies are removed from the main dependency tables, and from every dev group if `dev`
+/// is set. They are removed from the extras listing them too.
+pub fn fix(path: &Path, udeps: &[String], dev: bool, dry_run: bool) -> Result<Fix> {
+    let original =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let packages: BTreeSet<NormalizedName> =
+        udeps.iter().map(|dep| NormalizedName::new(dep)).collect();
+    let (fixed, removed) = remove_dependencies(&original, &packages, dev)
+        .with_context(|| format!("failed to parse {}", path.display()))?;
+
+    let missing = udeps
+        .iter()
+        .filter(|dep| {
+            let dep = NormalizedName::new(dep);
+            !removed
+                .iter()
+                .any(|r| NormalizedName::new(&r.package) == dep)
+        })
+        .cloned()
+        .collect();
+    if !dry_run && fixed != original {
+        fs::write(path, &fixed).with_context(|| format!("failed to write {}", path.display()))?;
+    }
+    Ok(Fix {
+        path: path.to_path_buf(),
+        original,
+        fixed,
+        removed,
+        missing,
+    })
+}
+
+/// Remove `packages` from a pyproject.toml document, returning the new document and what was
+/// removed.
+pub(crate) fn remove_dependencies(
+    toml: &str,
+    packages: &BTreeSet<NormalizedName>,
+    dev: bool,
+) -> Result<(String, Vec<Removal>)> {
+    let mut doc = toml.parse::<DocumentMut>()?;
+    let mut removed = Vec::new();
+
+    let (tables, arrays) = if dev {
+        (
+            [MAIN_TABLES, DEV_TABLES].concat(),
+            [MAIN_ARRAYS, DEV_ARRAYS].concat(),
+        )
+    } else {
+        (MAIN_TABLES.to_vec(), MAIN_ARRAYS.to_vec())
+    };
+    for pattern in tables {
+        for location in expand(doc.as_item(), pattern) {
+            let Some(table) =
+                get_mut(doc.as_item_mut(), &location).and_then(Item::as_table_like_mut)
+            else {
+                continue;
+            };
+            let keys: Vec<String> = table
+                .iter()
+                .map(|(key, _)| key.to_string())
+                .filter(|key| packages.contains(&NormalizedName::new(key)))
+                .collect();
+            for key in keys {
+                table.remove(&key);
+                removed.push(Removal {
+                    package: key,
+                    location: location.clone(),
+                });
+            }
+        }
+    }
+    for pattern in arrays {
+        for location in expand(doc.as_item(), pattern) {
+            let Some(array) = get_mut(doc.as_item_mut(), &location).and_then(Item::as_array_mut)
+            else {
+                continue;
+            };
+            for package in remove_requirements(array, packages) {
+                removed.push(Removal {
+                    package,
+                    location: location.clone(),
+                });
+            }
+        }
+    }
+
+    // Extras listing a removed dependency would refer to a package which is not declared anymore
+    let gone: BTreeSet<NormalizedName> = removed
+        .iter()
+        .map(|removal| NormalizedName::new(&removal.package))
+        .collect();
+    for pattern in EXTRAS {
+        for location in expand(doc.as_item(), pattern) {
+            let Some(array) = get_mut(doc.as_item_mut(), &location).and_then(Item::as_array_mut)
+            else {
+                continue;
+            };
+            for package in remove_requirements(array, &gone) {
+                removed.push(Removal {
+                    package,
+                    location: location.clone(),
+                });
+            }
+        }
+    }
+    Ok((doc.to_string(), removed))
+}
+
+/// Remove the requirements on `packages` from an array of PEP 508 requirements, returning the
+/// names they were spelled with.
+fn remove_requirements(array: &mut Array, packages: &BTreeSet<NormalizedName>) -> Vec<String> {
+    let mut removed = Vec::new();
+    let mut i = 0;
+    while i < array.len() {
+        // Anything else is kept, e.g. `{ include-group = "test" }`
+        let Some(req) = array
+            .get(i)
+            .and_then(Value::as_str)
+            .and_then(Requirement::parse)
+            .filter(|req| packages.contains(&req.normalized))
+        else {
+            i += 1;
+            continue;
+        };
+        let value = array.remove(i);
+        if let Some(next) = array.get_mut(i) {
+            let prefix = value.decor().prefix().and_then(RawString::as_str);
+            let next_prefix = next.decor().prefix().and_then(RawString::as_str);
+            if let (Some(prefix), Some(next_prefix)) = (prefix, next_prefix) {
+                let prefix = joined_prefix(prefix, next_prefix);
+                next.decor_mut().set_prefix(prefix);
+            }
+        }
+        removed.push(req.name);
+    }
+    removed
+}
+
+/// The prefix of the entry following a removed one in an array, given the prefixes of both.
+///
+/// The first line of `next` is the rest of the removed entry's line, e.g. its comment, so it goes
+/// with it, as do the comments above the removed entry. The comments above the next entry stay.
+fn joined_prefix(removed: &str, next: &str) -> String {
+    let Some((_, next_lines)) = next.split_once('\n') else {
+        // Both are on the same line, so the next entry takes the removed one's place
+        return removed.to_string();
+    };
+    // The end of the line before the removed entry, e.g. the previous entry's comment
+    let line_end = match removed.find('\n') {
+        Some(end) => &removed[..=end],
+        None => "\n",
+    };
+    format!("{line_end}{next_lines}")
+}
+
+/// Expand the `*` components of a dotted path into the keys present in the document.
+fn expand(root: &Item, pattern: &str) -> Vec<String> {
+    let mut paths = vec![(String::new(), root)];
+    for key in pattern.split('.') {
+        let mut next = Vec::new();
+        for (path, item) in paths {
+            let join = |key: &str| {
+                if path.is_empty() {
+                    key.to_string()
+                } else {
+                    format!("{path}.{key}")
+                }
+            };
+            if key == "*" {
+                for (key, child) in item.as_table_like().into_iter().flat_map(|t| t.iter()) {
+                    next.push((join(key), child));
+                }
+            } else if let Some(child) = item.get(key) {
+                next.push((join(key), child));
+            }
+        }
+        paths = next;
+    }
+    paths.into_iter().map(|(path, _)| path).collect()
+}
+
+fn get_mut<'a>(item: &'a mut Item, path: &str) -> Option<&'a mut Item> {
+    path.split('.')
+        .try_fold(item, |item, key| item.get_mut(key))
+}
+
+/// Offer to refresh poetry.lock after pyproject.toml changed, if Poetry is available.
+///
+/// The user is only asked when running interactively. Otherwise, the command to run is logged.
+pub fn offer_lock_refresh(lock: &Path) -> Result<()> {
+    if !lock.is_file() {
+        return Ok(());
+    }
+    let sh = Shell::new()?;
+    if let Some(dir) = lock.parent().filter(|p| !p.as_os_str().is_empty()) {
+        sh.change_dir(dir);
+    }
+    let Ok(version) = cmd!(sh, "poetry --version").quiet().ignore_stderr().read() else {
+        info!("Poetry is not available, so poetry.lock was not refreshed");
+        return Ok(());
+    };
+    // Poetry 1 updates every locked version unless told not to
+    let args: &[&str] = if version.contains("version 1.") {
+        &["lock", "--no-update"]
+    } else {
+        &["lock"]
+    };
+
+    let command = format!("poetry {}", args.join(" "));
+    if !(io::stdin().is_terminal() && io::stderr().is_terminal()) {
+        eprintln!("poetry.lock may be out of date. Run `{command}` to refresh it.");
+        return Ok(());
+    }
+    eprint!("Refresh poetry.lock with `{command}`? [y/N] ");
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    if matches!(answer.trim(), "y" | "Y" | "yes") {
+        cmd!(sh, "poetry {args...}").run()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn packages(names: &[&str]) -> BTreeSet<NormalizedName> {
+        names.iter().map(|name| NormalizedName::new(name)).collect()
+    }
+
+    #[test]
+    fn test_remove_poetry() {
+        let toml = r#"[tool.poetry.dependencies]
+python = "^3.10"
+# HTTP client
+requests = "^2.31"
+PyYAML = "*" # config
+asyncpg = { version = "*", optional = true }
+
+[tool.poetry.group.test.dependencies]
+pytest = "*"
+pyyaml = "*"
+"#;
+        let (fixed, removed) =
+            remove_dependencies(toml, &packages(&["requests", "pyyaml"]), false).unwrap();
+        assert_eq!(
+            fixed,
+            r#"[tool.poetry.dependencies]
+python = "^3.10"
+asyncpg = { version = "*", optional = true }
+
+[tool.poetry.group.test.dependencies]
+pytest = "*"
+pyyaml = "*"
+"#
+        );
+        assert_eq!(
+            removed,
+            [
+                Removal {
+                    package: "requests".to_string(),
+                    location: "tool.poetry.dependencies".to_string()
+                },
+                Removal {
+                    package: "PyYAML".to_string(),
+                    location: "tool.poetry.dependencies".to_string()
+                },
+            ]
+        );
+
+        let (fixed, removed) = remove_dependencies(toml, &packages(&["pyyaml"]), true).unwrap();
+        assert!(fixed.ends_with("[tool.poetry.group.test.dependencies]\npytest = \"*\"\n"));
+        assert_eq!(removed.len(), 2);
+        assert_eq!(removed[1].location, "tool.poetry.group.test.dependencies");
+    }
+
+    #[test]
+    fn test_remove_pep621() {
+        let toml = r#"[project]
+name = "app"
+dependencies = [
+    "requests>=2.31",  # HTTP
+    "PyYAML",
+    "sqlalchemy[asyncio]>=2",
+]
+
+[dependency-groups]
+test = ["pytest", "Requests", { include-group = "lint" }]
+lint = ["ruff"]
+"#;
+        let (fixed, removed) =
+            remove_dependencies(toml, &packages(&["requests", "ruff"]), true).unwrap();
+        assert_eq!(
+            fixed,
+            r#"[project]
+name = "app"
+dependencies = [
+    "PyYAML",
+    "sqlalchemy[asyncio]>=2",
+]
+
+[dependency-groups]
+test = ["pytest", { include-group = "lint" }]
+lint = []
+"#
+        );
+        let locations: Vec<&str> = removed.iter().map(|r| r.location.as_str()).collect();
+        assert_eq!(
+            locations,
+            [
+                "project.dependencies",
+                "dependency-groups.test",
+                "dependency-groups.lint"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_remove_keeps_comments() {
+        let toml = r#"[project]
+dependencies = [
+    "click",  # CLI
+    # HTTP client
+    "requests",  # HTTP
+    # YAML parsing
+    "PyYAML",
+]
+"#;
+        let (fixed, _) = remove_dependencies(toml, &packages(&["requests"]), false).unwrap();
+        assert_eq!(
+            fixed,
+            r#"[project]
+dependencies = [
+    "click",  # CLI
+    # YAML parsing
+    "PyYAML",
+]
+"#
+        );
+        let (fixed, _) = remove_dependencies(toml, &packages(&["click"]), false).unwrap();
+        assert!(
+            fixed.starts_with("[project]\ndependencies = [\n    # HTTP client\n    \"requests\"")
+        );
+
+        let (fixed, _) = remove_dependencies(
+            "[project]\ndependencies = [\"a\", \"b\"]\n",
+            &packages(&["a"]),
+            false,
+        )
+        .unwrap();
+        assert_eq!(fixed, "[project]\ndependencies = [\"b\"]\n");
+    }
+
+    #[test]
+    fn test_remove_extras() {
+        let toml = r#"[tool.poetry.dependencies]
+python = "^3.10"
+asyncpg = { version = "*", optional = true }
+redis = { version = "*", optional = true }
+
+[tool.poetry.extras]
+pg = ["asyncpg"]
+all = ["asyncpg", "redis"]
+
+[project.optional-dependencies]
+pg = ["asyncpg>=0.29"]
+"#;
+        let (fixed, removed) = remove_dependencies(toml, &packages(&["asyncpg"]), false).unwrap();
+        assert_eq!(
+            fixed,
+            r#"[tool.poetry.dependencies]
+python = "^3.10"
+redis = { version = "*", optional = true }
+
+[tool.poetry.extras]
+pg = []
+all = ["redis"]
+
+[project.optional-dependencies]
+pg = []
+"#
+        );
+        let locations: Vec<&str> = removed.iter().map(|r| r.location.as_str()).collect();
+        assert_eq!(
+            locations,
+            [
+                "tool.poetry.dependencies",
+                "tool.poetry.extras.pg",
+                "tool.poetry.extras.all",
+                "project.optional-dependencies.pg"
+            ]
+        );
+        // Extras only lose the dependencies which were removed
+        let (fixed, removed) = remove_dependencies(
+            "[tool.poetry.extras]\npg = [\"asyncpg\"]\n",
+            &packages(&["asyncpg"]),
+            false,
+        )
+        .unwrap();
+        assert_eq!(fixed, "[tool.poetry.extras]\npg = [\"asyncpg\"]\n");
+        assert!(removed.is_empty());
+    }
+
+    #[test]
+    fn test_fix() {
+        let root = tempfile::tempdir().unwrap();
+        let path = root.path().join("pyproject.toml");
+        let toml = "[project]\ndependencies = [\"requests\", \"click\"]\n";
+        fs::write(&path, toml).unwrap();
+        let udeps = ["requests".to_string(), "Flask".to_string()];
+
+        let fix = super::fix(&path, &udeps, false, true).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), toml);
+        assert_eq!(fix.missing, ["Flask"]);
+        assert!(
+            fix.diff()
+                .contains("-dependencies = [\"requests\", \"click\"]")
+        );
+
+        super::fix(&path, &udeps, false, false).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "[project]\ndependencies = [\"click\"]\n"
+        );
+    }
+}
@@ -0,0 +1,152 @@
+//! Reading and pruning .poetryudepsignore, the list of packages which are never reported.
+
+use std::{collections::BTreeSet, fs, io, path::Path};
+
+use crate::name::NormalizedName;
+
+/// A package listed in the ignore file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// The package, as spelled in the file.
+    pub package: String,
+    pub normalized: NormalizedName,
+    /// The line it is listed on, counting from 1.
+    pub line: usize,
+}
+
+fn is_entry(line: &str) -> bool {
+    let line = line.trim();
+    !(line.is_empty() || line.starts_with('#'))
+}
+
+/// Read the packages listed in the ignore file at `path`, skipping empty lines and comments.
+///
+/// A missing file lists no packages.
+pub fn read(path: &Path) -> io::Result<Vec<Entry>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    Ok(contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| is_entry(line))
+        .map(|(i, line)| {
+            let package = line.trim().to_string();
+            Entry {
+                normalized: NormalizedName::new(&package),
+                package,
+                line: i + 1,
+            }
+        })
+        .collect())
+}
+
+/// Remove the entries on `lines` from `contents`.
+///
+/// The comments just above an entry, like the reasons `--interactive` writes, go with it, unless
+/// another entry which is kept follows it directly: they are then the heading of a block of
+/// entries, which only goes with the whole block.
+fn prune(contents: &str, lines: &BTreeSet<usize>) -> String {
+    let all: Vec<&str> = contents.lines().collect();
+    let mut keep = vec![true; all.len()];
+    let removed: Vec<usize> = lines
+        .iter()
+        .filter_map(|line| line.checked_sub(1))
+        .filter(|i| all.get(*i).is_some_and(|l| is_entry(l)))
+        .collect();
+    for &i in &removed {
+        keep[i] = false;
+    }
+    // From the bottom, so that what follows each entry is the lines which remain
+    for &i in removed.iter().rev() {
+        let next = (i + 1..all.len()).find(|j| keep[*j]);
+        if next.is_some_and(|j| is_entry(all[j])) {
+            continue;
+        }
+        for j in (0..i).rev() {
+            if !keep[j] && is_entry(all[j]) {
+                // Another entry of the block being removed
+                continue;
+            }
+            if !all[j].trim_start().starts_with('#') {
+                break;
+            }
+            keep[j] = false;
+        }
+    }
+    let mut pruned = String::new();
+    for (line, _) in all.iter().zip(keep).filter(|(_, keep)| *keep) {
+        pruned.push_str(line);
+        pruned.push('\n');
+    }
+    if !contents.ends_with('\n') {
+        pruned.pop();
+    }
+    pruned
+}
+
+/// Remove the entries on `lines` from the ignore file at `path`.
+pub fn prune_file(path: &Path, lines: &BTreeSet<usize>) -> io::Result<()> {
+    let contents = fs::read_to_string(path)?;
+    fs::write(path, prune(&contents, lines))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_read() {
+        let root = tempfile::tempdir().unwrap();
+        let path = root.path().join(".poetryudepsignore");
+        assert_eq!(read(&path).unwrap(), []);
+        fs::write(&path, "# Drivers\nasyncpg\n\n  PyYAML \n").unwrap();
+        let entries: Vec<(String, usize)> = read(&path)
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.normalized.to_string(), entry.line))
+            .collect();
+        assert_eq!(
+            entries,
+            [("asyncpg".to_string(), 2), ("pyyaml".to_string(), 4)]
+        );
+    }
+
+    #[test]
+    fn test_prune() {
+        let contents =
+            "# Drivers\nasyncpg\npsycopg\n\n# loaded by sqlalchemy\ngreenlet\n# a plugin\nPyYAML\n";
+        assert_eq!(
+            prune(contents, &BTreeSet::from([2, 6, 8])),
+            "# Drivers\npsycopg\n\n"
+        );
+        // Only entries are removed
+        assert_eq!(
+            prune(contents, &BTreeSet::from([1, 3])),
+            "# Drivers\nasyncpg\n\n# loaded by sqlalchemy\ngreenlet\n# a plugin\nPyYAML\n"
+        );
+        assert_eq!(prune("asyncpg", &BTreeSet::from([1])), "");
+        // A heading goes with the whole block, but not with part of it
+        assert_eq!(
+            prune(contents, &BTreeSet::from([2, 3])),
+            "\n# loaded by sqlalchemy\ngreenlet\n# a plugin\nPyYAML\n"
+        );
+        assert_eq!(
+            prune(
+                "# Drivers\nasyncpg\npsycopg\nredis\n",
+                &BTreeSet::from([2, 3])
+            ),
+            "# Drivers\nredis\n"
+        );
+        assert_eq!(
+            prune(
+                "# Drivers\nasyncpg\n# async\npsycopg\n",
+                &BTreeSet::from([2, 4])
+            ),
+            ""
+        );
+    }
+}
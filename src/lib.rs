@@ -1,50 +1,261 @@
 use std::{
-    collections::BTreeMap,
-    fs::{self, File},
-    io::{self, BufRead, BufReader, Read},
+    collections::{BTreeMap, BTreeSet, HashMap},
+    convert::Infallible,
+    fmt,
+    io::{self, BufRead, IsTerminal, Write},
+    num::NonZeroUsize,
     path::{Path, PathBuf},
+    sync::{
+        Mutex, PoisonError,
+        atomic::{AtomicUsize, Ordering},
+    },
     thread,
+    time::Instant,
 };
 
-use anyhow::{Result, bail};
-use clap::Parser;
+use anyhow::{Context, Result, bail};
+use clap::{Parser, Subcommand};
 use clap_verbosity_flag::Verbosity;
-use ignore::{WalkBuilder, types::TypesBuilder};
-use toml::Value;
-use tracing::{debug, error, info};
+use ignore::{
+    WalkBuilder,
+    gitignore::{Gitignore, GitignoreBuilder},
+    overrides::{Override, OverrideBuilder},
+    types::TypesBuilder,
+};
+use tracing::{debug, error, info, warn};
 use xshell::{Shell, cmd};
 
+mod cache;
+mod config;
+mod encoding;
+mod fix;
+mod ignores;
+mod lock;
+mod lsp;
+mod manifest;
+mod name;
 mod name_map;
 mod parser;
-use crate::name_map::KNOWN_NAMES;
-use crate::parser::{ImportStatement, parse_python_file};
+mod report;
+mod review;
+mod source_tree;
+mod stdlib;
+mod venv;
+mod watch;
+mod why;
+use crate::cache::Cache;
+use crate::config::Config;
+pub use crate::fix::{Fix, Removal};
+use crate::lock::LockGraph;
+pub use crate::manifest::Location;
+use crate::manifest::{Manifest, PYPROJECT, Requirement};
+pub use crate::manifest::{ManifestKind, Suppression};
+pub use crate::name::NormalizedName;
+use crate::name_map::known_module;
+pub use crate::parser::ImportStatement;
+pub use crate::report::{
+    Checked, FileWarning, Finding, FindingKind, Format, Report, SCHEMA_VERSION, Statistics, Usage,
+    to_github, to_json, to_junit, to_sarif,
+};
+pub use crate::review::{Choice, Review};
+use crate::stdlib::is_stdlib;
+use crate::watch::Watcher;
+pub use crate::why::{Evidence, Explanation};
 
 const IGNORE_FILE: &str = ".poetryudepsignore";
+const LOCK_FILE: &str = "poetry.lock";
+
+/// Top-level module -> the first place the project imports it, for modules not provided by any
+/// declared dependency.
+type Unresolved = BTreeMap<String, (PathBuf, usize)>;
+
+/// An import, the file it was found in, and the dependency whose code contains it (or `None` for
+/// project code).
+type Import = (ImportStatement, PathBuf, Option<NormalizedName>);
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct Cli {
     #[clap(flatten)]
     pub verbose: Verbosity,
-    #[arg(short = 'e', long)]
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    #[arg(short = 'e', long, global = true)]
     /// Look for dependency usage in the poetry virtualenv.
     ///
     /// Assumes you have already installed all dependencies using poetry. It
-    /// will check the directory specified by `poetry env info -p`.
+    /// will check the directory specified by `poetry env info -p`. Only the
+    /// files installed by declared dependencies are scanned, and a dependency
+    /// imported by another dependency only counts as used if that other
+    /// dependency is itself used.
     pub virtualenv: bool,
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
+    /// Use poetry.lock to find dependencies required by other used dependencies.
+    ///
+    /// This works offline: neither a virtualenv nor `poetry` are needed. A
+    /// dependency which is not imported, but which another used dependency
+    /// requires, is reported along with the chain of packages requiring it.
+    pub lock: bool,
+    #[arg(short, long, global = true)]
     /// Look for unused dependencies in dev-dependencies.
     ///
-    /// Many projects include dev deps like CLI tools that are intentionally
-    /// not directly used in the codebase.
+    /// This covers every dependency group other than the main one. Many
+    /// projects include dev deps like CLI tools that are intentionally not
+    /// directly used in the codebase.
     pub dev: bool,
-    #[arg(long = "no-ignore")]
-    /// Do not ignore the packages in .poetryudepsignore
+    #[arg(short, long, value_enum, default_value_t, global = true)]
+    /// The file declaring the project's dependencies.
+    pub manifest: ManifestKind,
+    #[arg(
+        long,
+        global = true,
+        value_name = "DIR",
+        conflicts_with = "manifest_path"
+    )]
+    /// Check the project in this directory, instead of the current one.
+    ///
+    /// Its manifest, .poetryudepsignore and virtualenv are found there, and
+    /// the paths printed are relative to it.
+    pub project: Option<PathBuf>,
+    #[arg(long, conflicts_with_all = ["manifest_path", "fix", "interactive"])]
+    /// Check every project with a pyproject.toml under the project directory.
+    ///
+    /// Directories ignored by .gitignore and hidden ones are skipped. Each
+    /// project is checked against its own manifest and .poetryudepsignore,
+    /// and its files are scanned once, excluding those of nested projects.
+    /// The findings are combined in one report, grouped by project, with
+    /// paths relative to the workspace root.
+    pub workspace: bool,
+    #[arg(long, global = true, value_name = "FILE", value_parser = manifest_path)]
+    /// Check the project declared by this manifest, e.g. path/to/pyproject.toml.
+    ///
+    /// The project is the directory holding it, and unless `--manifest` is
+    /// given, the kind of manifest is told by the file's name. A requirements
+    /// file, e.g. requirements-dev.txt, is read alone, along with the files
+    /// it includes, rather than with every requirements*.txt file.
+    pub manifest_path: Option<PathBuf>,
+    #[arg(long)]
+    /// Remove the unused dependencies from pyproject.toml.
+    ///
+    /// Entries are removed from the main dependency tables, and with `--dev`
+    /// from every dependency group, keeping the rest of the file's formatting
+    /// and comments. If poetry.lock exists and Poetry is available, you are
+    /// offered to refresh it afterwards.
+    pub fix: bool,
+    #[arg(long, requires = "fix")]
+    /// Print the changes `--fix` would make as a diff, without writing them.
+    pub dry_run: bool,
+    #[arg(short, long, value_enum, default_value_t, conflicts_with_all = ["fix", "interactive"])]
+    /// How to print the findings.
+    pub format: Format,
+    #[arg(long)]
+    /// Report every kind of finding, not only the unused dependencies.
+    ///
+    /// Missing and misplaced dependencies are then printed too, and fail the
+    /// run, while transitive dependencies and stale .poetryudepsignore
+    /// entries are printed as notes. The `json` and `sarif` formats include
+    /// every finding either way, but only fail on them with this flag.
+    pub all_findings: bool,
+    #[arg(short, long, conflicts_with = "fix")]
+    /// Review each unused dependency, choosing to remove, ignore or skip it.
+    ///
+    /// The evidence for each is shown first: its groups and extras, the
+    /// module names tried and the imports which nearly used it. Ignored
+    /// dependencies are added to .poetryudepsignore, along with the reason
+    /// given. The choices are applied once every dependency is reviewed.
+    pub interactive: bool,
+    #[arg(long, conflicts_with_all = ["workspace", "watch", "no_ignore"])]
+    /// Remove the stale entries from .poetryudepsignore.
+    ///
+    /// An entry is stale if its package is neither declared nor imported, or
+    /// if it is used: ignoring it then only risks hiding a problem later.
+    /// Stale entries are reported with `--all-findings` either way.
+    pub prune_ignore: bool,
+    #[arg(long, global = true, value_name = "GLOB")]
+    /// Only count files matching this glob as package code.
+    ///
+    /// Only package code can credit main dependencies: other files, like
+    /// tests, are still scanned, but only credit dev dependencies. Globs
+    /// follow the .gitignore syntax, relative to the project root, and may be
+    /// given more than once. They replace `include` in [tool.poetry-udeps],
+    /// which defaults to the packages configured for Poetry, setuptools or
+    /// Hatch, or else to every file.
+    pub include: Vec<String>,
+    #[arg(long, global = true, value_name = "GLOB")]
+    /// Do not scan files matching this glob, e.g. `migrations/`.
+    ///
+    /// Globs follow the .gitignore syntax, relative to the project root, and
+    /// may be given more than once. They add to `exclude` in
+    /// [tool.poetry-udeps].
+    pub exclude: Vec<String>,
+    #[arg(long, conflicts_with_all = ["fix", "interactive", "format"])]
+    /// Keep checking the project whenever its files change.
+    ///
+    /// Every finding is printed first, and then how the findings changed
+    /// after each change, e.g. `requests: now unused`. Only the files which
+    /// changed are parsed again, thanks to the cache.
+    pub watch: bool,
+    #[arg(long, global = true, value_name = "DIR")]
+    /// Where to cache the imports parsed from each file.
+    ///
+    /// Only files which changed since the last run are parsed again. By
+    /// default, the cache is kept in $XDG_CACHE_HOME/poetry-udeps, or
+    /// ~/.cache/poetry-udeps.
+    pub cache_dir: Option<PathBuf>,
+    #[arg(long, global = true, conflicts_with = "cache_dir")]
+    /// Parse every file, without reading or writing the cache.
+    pub no_cache: bool,
+    #[arg(long, global = true)]
+    /// Fail if a project file cannot be read, decoded or parsed.
+    ///
+    /// By default, such files are skipped (or decoded lossily) with a warning.
+    pub strict: bool,
+    #[arg(long = "no-ignore", global = true)]
+    /// Report every dependency, even those which are ignored.
+    ///
+    /// Neither .poetryudepsignore nor `# poetry-udeps: ignore` comments are
+    /// honored, so nothing is hidden.
     pub no_ignore: bool,
 }
 
-fn get_venv_path() -> Result<String> {
+#[derive(Subcommand)]
+pub enum Command {
+    /// Explain why a dependency is considered used or unused.
+    ///
+    /// Prints the module names the dependency may be imported by, where each
+    /// came from, and every import which credits it. If none does, the imports
+    /// which nearly did are shown instead.
+    Why {
+        /// The dependency to explain, as declared in the manifest.
+        package: String,
+    },
+    /// Run a language server over stdio, for editors to show the findings.
+    ///
+    /// Diagnostics are published on the manifest for unused dependencies, and on
+    /// the first import of each missing one, with code actions to remove or
+    /// ignore the dependency. The project is checked again as documents are
+    /// saved.
+    Lsp,
+}
+
+/// Parse `--manifest-path`, which must name a kind of manifest we can read.
+fn manifest_path(path: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(path);
+    if ManifestKind::from_path(&path).is_none() {
+        return Err(
+            "expected pyproject.toml, setup.cfg, Pipfile or a requirements file".to_string(),
+        );
+    }
+    if !path.is_file() {
+        return Err("no such file".to_string());
+    }
+    Ok(path)
+}
+
+fn get_venv_path(root: &Path) -> Result<String> {
     let sh = Shell::new()?;
+    sh.change_dir(root);
 
     Ok(cmd!(sh, "poetry env info -p").quiet().read()?)
 }
@@ -54,333 +265,2081 @@ enum DepType {
     Dev,
 }
 
-/// Returns two maps (one for core deps, one for dev-deps).
-///
-/// The maps are filled with either the original package name -> None, or with
-/// the alias -> [package names]. This helps us quickly determine which original
-/// dependency to eliminate if either the original package name or alias is
-/// found.
+/// Where a module name provided by a dependency was learned from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AliasSource {
+    /// The distribution name itself.
+    Name,
+    /// The embedded name map.
+    NameMap,
+    /// The packages in the source tree of a path dependency.
+    SourceTree,
+    /// The files the distribution installed in the virtualenv.
+    Metadata,
+    /// A mapping given to [`Analyzer::mapping`].
+    Override,
+}
+
+impl fmt::Display for AliasSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AliasSource::Name => "distribution name",
+            AliasSource::NameMap => "name map",
+            AliasSource::SourceTree => "source tree",
+            AliasSource::Metadata => "installed RECORD",
+            AliasSource::Override => "mapping override",
+        })
+    }
+}
+
+/// The declared dependencies of one kind, and the names they may be imported by.
+#[derive(Debug, Default)]
+struct Dependencies {
+    /// Normalized name -> the name as spelled in the manifest.
+    declared: BTreeMap<NormalizedName, String>,
+    /// Alias -> the dependencies it refers to.
+    ///
+    /// Aliases are the modules a dependency provides. Every dependency is an alias of itself.
+    /// This helps us quickly determine which original dependency to eliminate if either the
+    /// original package name or alias is found.
+    ///
+    /// We do not simply track the aliases alone, as reporting an alias as obsolete
+    /// is not as straightforward to the user which line to eliminate from their
+    /// pyproject.toml.
+    aliases: BTreeMap<NormalizedName, BTreeSet<NormalizedName>>,
+    /// Dependency -> the modules it provides, and where each was learned from.
+    provides: BTreeMap<NormalizedName, BTreeMap<String, AliasSource>>,
+}
+
+impl Dependencies {
+    /// Returns the dependencies credited when `alias` is found.
+    fn credited(&self, alias: &NormalizedName) -> impl Iterator<Item = &NormalizedName> {
+        self.aliases.get(alias).into_iter().flatten()
+    }
+
+    /// Record that `package` provides the module `alias`, if `package` is declared.
+    fn add_alias(&mut self, package: &NormalizedName, alias: &str, source: AliasSource) {
+        if self.declared.contains_key(package) {
+            self.aliases
+                .entry(NormalizedName::new(alias))
+                .or_default()
+                .insert(package.clone());
+            self.provides
+                .entry(package.clone())
+                .or_default()
+                .entry(alias.to_string())
+                .or_insert(source);
+        }
+    }
+
+    /// Replace every module `package` was thought to provide with `modules`.
+    fn override_aliases(&mut self, package: &NormalizedName, modules: &[String]) {
+        if !self.declared.contains_key(package) {
+            return;
+        }
+        for credited in self.aliases.values_mut() {
+            credited.remove(package);
+        }
+        self.aliases.retain(|_, credited| !credited.is_empty());
+        self.provides.remove(package);
+        for module in modules {
+            self.add_alias(package, module, AliasSource::Override);
+        }
+    }
+}
+
+/// Find the dependencies credited by an import.
 ///
-/// We do not simply track the aliases alone, as reporting an alias as obsolete
-/// is not as straightforward to the user which line to eliminate from their
-/// pyproject.toml.
-fn get_dependencies(file: &Path, deps: &DepType) -> Result<Option<BTreeMap<String, Vec<String>>>> {
-    let toml = fs::read_to_string(file)?;
+/// Only the longest alias provided by any dependency counts, so that importing
+/// `google.cloud.storage` credits `google-cloud-storage`, and not other dependencies providing
+/// `google` or `google.cloud`.
+fn credited_by<'a>(import: &ImportStatement, deps: &[&'a Dependencies]) -> Vec<&'a NormalizedName> {
+    import_aliases(import)
+        .iter()
+        .map(|alias| {
+            deps.iter()
+                .flat_map(|d| d.credited(alias))
+                .collect::<Vec<_>>()
+        })
+        .find(|credited| !credited.is_empty())
+        .unwrap_or_default()
+}
 
+/// Returns the dependencies of one kind (core deps or dev-deps).
+fn get_dependencies(manifest: &Manifest, deps: &DepType) -> Result<Option<Dependencies>> {
     // TODO: map package name to actual module name.
     // Ref: https://stackoverflow.com/a/54853084
-    let value = toml.parse::<Value>()?;
-    let dep_table: Vec<String> = match deps {
-        DepType::Main => {
-            match value
-                .get("tool")
-                .and_then(|tool| tool.get("poetry"))
-                .and_then(|poetry| poetry.get("dependencies"))
-                .and_then(|deps| deps.as_table())
-            {
-                Some(deps) => deps.keys().map(std::borrow::ToOwned::to_owned).collect(),
-                // Check poetry >=2.0
-                None => {
-                    if let Some(deps) = value
-                        .get("project")
-                        .and_then(|dev| dev.get("dependencies"))
-                        .and_then(|dependencies| dependencies.as_array())
-                        .map(|dep_array: &Vec<Value>| {
-                            dep_array
-                                .iter()
-                                .filter_map(|val| {
-                                    val.as_str().and_then(|s| {
-                                        pep_508::parse(s).ok().map(|req| req.name.to_string())
-                                    })
-                                })
-                                .collect()
-                        })
-                    {
-                        deps
-                    } else {
-                        bail!("failed to parse dependencies from pyproject.toml")
+    let dep_table: Vec<&Requirement> = match deps {
+        DepType::Main => match manifest.main() {
+            Some(reqs) => reqs.iter().collect(),
+            None => bail!("failed to parse dependencies from pyproject.toml"),
+        },
+        DepType::Dev => {
+            let Some(reqs) = manifest.dev() else {
+                info!("failed to parse dev dependencies from pyproject.toml");
+                return Ok(None);
+            };
+            reqs
+        }
+    };
+    let mut dependencies = Dependencies::default();
+
+    // Generate a list of possible aliases for the package
+    for req in dep_table {
+        let package = req.normalized.clone();
+        dependencies
+            .declared
+            .insert(package.clone(), req.name.clone());
+        dependencies.add_alias(&package, &req.name, AliasSource::Name);
+        if let Some(alias) = known_module(package.as_str()) {
+            dependencies.add_alias(&package, &alias, AliasSource::NameMap);
+        }
+        // The modules of a path dependency are taken from its source tree
+        if let Some(path) = &req.path {
+            match source_tree::provided_modules(path) {
+                Ok(modules) => {
+                    for module in modules {
+                        dependencies.add_alias(&package, &module, AliasSource::SourceTree);
                     }
                 }
+                Err(e) => warn!(package = req.name, error = %e, "Unable to read path dependency"),
             }
         }
-        DepType::Dev => {
-            // Check poetry >=1.0,<1.2's dev-dependencies
-            match value
-                .get("tool")
-                .and_then(|tool| tool.get("poetry"))
-                .and_then(|poetry| poetry.get("dev-dependencies"))
-                .and_then(|dev| dev.as_table())
-            {
-                Some(dev) => dev.keys().map(std::borrow::ToOwned::to_owned).collect(),
-                // Check poetry >=1.2.0's dependency groups
-                None => {
-                    if let Some(deps) = value
-                        .get("tool")
-                        .and_then(|tool| tool.get("poetry"))
-                        .and_then(|poetry| poetry.get("group"))
-                        .and_then(|group| group.get("dev"))
-                        .and_then(|dev| dev.get("dependencies"))
-                        .and_then(|dependencies| dependencies.as_table())
-                    {
-                        deps.keys().map(std::borrow::ToOwned::to_owned).collect()
-                    } else {
-                        info!("failed to parse dev dependencies from pyproject.toml");
-                        return Ok(None);
+    }
+    Ok(Some(dependencies))
+}
+
+// Read the packages listed in the ignorefile, if there is one.
+fn ignored_packages(path: &Path) -> io::Result<BTreeSet<NormalizedName>> {
+    let entries = ignores::read(path)?;
+    debug!(ignored = ?entries);
+    Ok(entries.into_iter().map(|entry| entry.normalized).collect())
+}
+
+/// Describe an import of `module` which no declared dependency provides.
+fn missing(module: String, path: &Path, line: usize) -> Finding {
+    let mut location = Location::file(path);
+    location.line = Some(line);
+    Finding {
+        location: Some(location),
+        ..Finding::new(FindingKind::Missing, module, None)
+    }
+}
+
+/// Describe an entry of the ignore file at `path`, relative to the project root, which ignores
+/// nothing: a package used in `group`, or with no group, one which is neither declared nor
+/// imported.
+fn stale_ignore(entry: &ignores::Entry, path: &Path, group: Option<&String>) -> Finding {
+    let mut location = Location::file(path);
+    location.line = Some(entry.line);
+    Finding {
+        location: Some(location),
+        ..Finding::new(FindingKind::StaleIgnore, &entry.package, group.cloned())
+    }
+}
+
+/// Find the group and requirement declaring `package`, among the groups being checked.
+///
+/// A dev dependency is reported in the first group declaring it.
+fn declaration<'a>(
+    manifest: &'a Manifest,
+    groups: &Groups,
+    package: &NormalizedName,
+    main: bool,
+) -> Option<(&'a String, &'a Requirement)> {
+    manifest
+        .groups
+        .iter()
+        .filter(|(group, _)| (*group == manifest::MAIN_GROUP) == main && groups.contains(group))
+        .find_map(|(group, reqs)| {
+            reqs.iter()
+                .find(|req| req.normalized == *package)
+                .map(|req| (group, req))
+        })
+}
+
+/// Describe a declared dependency which was checked.
+fn checked(
+    declaration: Option<(&String, &Requirement)>,
+    deps: &Dependencies,
+    package: &NormalizedName,
+    ignored: bool,
+    suppression: Option<&Suppression>,
+    usages: Vec<Usage>,
+) -> Checked {
+    Checked {
+        location: declaration.and_then(|(_, req)| req.location.clone()),
+        ignored: ignored || suppression.is_some(),
+        suppression: suppression.cloned(),
+        usages,
+        ..Checked::new(
+            &deps.declared[package],
+            declaration.map(|(group, _)| group.clone()),
+        )
+    }
+}
+
+/// Describe a problem with the declared dependency `package`.
+fn finding(
+    declaration: Option<(&String, &Requirement)>,
+    deps: &Dependencies,
+    package: &NormalizedName,
+    kind: FindingKind,
+    required_by: Vec<String>,
+) -> Finding {
+    Finding {
+        location: declaration.and_then(|(_, req)| req.location.clone()),
+        aliases: deps
+            .provides
+            .get(package)
+            .into_iter()
+            .flat_map(|provides| provides.keys().cloned())
+            .collect(),
+        required_by,
+        ..Finding::new(
+            kind,
+            &deps.declared[package],
+            declaration.map(|(group, _)| group.clone()),
+        )
+    }
+}
+
+/// Generate the possible module names an import statement may refer to, longest first.
+///
+/// These are every prefix of the imported module path, e.g.
+/// `from google.cloud.storage import Blob` may refer to `google.cloud.storage.Blob`,
+/// `google.cloud.storage`, `google.cloud` or `google`.
+fn import_aliases(import: &ImportStatement) -> Vec<NormalizedName> {
+    let path = if import.module.is_empty() {
+        import.package.clone()
+    } else {
+        // The imported name may itself be a module, e.g. `from google.cloud import bigquery`
+        format!("{}.{}", import.package, import.module)
+    };
+    let parts: Vec<&str> = path.split('.').collect();
+    (1..=parts.len())
+        .rev()
+        .map(|n| NormalizedName::new(&parts[..n].join(".")))
+        .collect()
+}
+
+/// Parse every file in `files` on a pool of threads, sending its imports along with its owner.
+///
+/// Returns how many files had their imports cached.
+fn scan_dependency_files(
+    files: &[(PathBuf, NormalizedName)],
+    cache: &Cache,
+    tx: &flume::Sender<Import>,
+) -> usize {
+    let workers = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    let chunk_size = files.len().div_ceil(workers).max(1);
+    let cached = AtomicUsize::new(0);
+    thread::scope(|s| {
+        for chunk in files.chunks(chunk_size) {
+            let tx = tx.clone();
+            let cached = &cached;
+            s.spawn(move || {
+                for (path, owner) in chunk {
+                    // RECORD may list files which have since been removed
+                    let parsed = match cache.parse(path) {
+                        Ok((parsed, hit)) => {
+                            cached.fetch_add(usize::from(hit), Ordering::Relaxed);
+                            parsed
+                        }
+                        Err(e) => {
+                            debug!(
+                                path = path.to_str(),
+                                error = format!("{e:#}"),
+                                "Skipping file"
+                            );
+                            continue;
+                        }
+                    };
+                    if let Some(warning) = parsed.warning {
+                        debug!(path = path.to_str(), warning);
+                    }
+                    for import in parsed.imports {
+                        // The receiver only hangs up if checking failed
+                        if tx
+                            .send((import, path.clone(), Some(owner.clone())))
+                            .is_err()
+                        {
+                            return;
+                        }
                     }
                 }
-            }
+            });
         }
+    });
+    cached.into_inner()
+}
+
+/// The declared dependencies of a project, and the dependency files to scan.
+struct Project {
+    manifest: Manifest,
+    main_deps: Dependencies,
+    dev_deps: Dependencies,
+    /// The files installed by declared dependencies, and the dependency they belong to.
+    files: Vec<(PathBuf, NormalizedName)>,
+}
+
+/// The outcome of scanning the project's own files.
+#[derive(Default)]
+struct Scan {
+    /// The project's own top-level modules and packages, importable from the project root or its
+    /// `src` directory.
+    local: BTreeSet<String>,
+    files: usize,
+    /// The files whose imports were cached.
+    cached: usize,
+    warnings: Vec<FileWarning>,
+}
+
+impl Scan {
+    fn warn(&mut self, path: &Path, message: impl Into<String>) {
+        let warning = FileWarning::new(path, message);
+        debug!("{warning}");
+        self.warnings.push(warning);
+    }
+}
+
+/// Parse every Python file under `roots` in parallel, sending its imports.
+///
+/// Files are sent with their path relative to the project `root`. Files which cannot be read or
+/// parsed are skipped with a warning.
+fn scan_project(
+    root: &Path,
+    roots: &[PathBuf],
+    excluded: &[PathBuf],
+    sources: &Sources,
+    cache: &Cache,
+    tx: &flume::Sender<Import>,
+) -> Result<Scan> {
+    let scan = Mutex::new(Scan::default());
+    let types = TypesBuilder::new().add_defaults().select("py").build()?;
+    let Some((first, rest)) = roots.split_first() else {
+        return Ok(Scan::default());
     };
-    let mut dependencies: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut walker = WalkBuilder::new(first);
+    for scan_root in rest {
+        walker.add(scan_root);
+    }
+    let excluded = excluded.to_vec();
+    let walker = walker
+        .standard_filters(true)
+        .types(types)
+        .overrides(sources.exclude.clone())
+        .filter_entry(move |entry| !excluded.iter().any(|dir| dir == entry.path()))
+        .build_parallel();
+    walker.run(|| {
+        let tx = tx.clone();
+        let scan = &scan;
+        Box::new(move |result| {
+            use ignore::WalkState::{Continue, Quit};
 
-    // Generate a list of possible aliases for the package
-    dep_table.iter().filter(|s| *s != "python").for_each(|s| {
-        let package = String::from(s);
-        dependencies.insert(package.clone(), vec![]);
-        let mut alias = KNOWN_NAMES.get(&package).map(|a| String::from(*a));
+            let lock = || scan.lock().unwrap_or_else(PoisonError::into_inner);
+            let dir = match result {
+                Ok(dir) if dir.file_type().is_some_and(|t| t.is_file()) => dir,
+                Ok(_) => return Continue,
+                Err(e) => {
+                    let path = match &e {
+                        ignore::Error::WithPath { path, .. } => relative(root, path),
+                        _ => PathBuf::new(),
+                    };
+                    lock().warn(&path, e.to_string());
+                    return Continue;
+                }
+            };
+            let path = relative(root, dir.path());
+            let (parsed, hit) = match cache.parse(dir.path()) {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    lock().warn(&path, format!("{e:#}"));
+                    return Continue;
+                }
+            };
+            if let Some(warning) = parsed.warning {
+                lock().warn(&path, warning);
+            }
 
-        // Or basic replacement
-        if alias.is_none() && package.contains('-') {
-            alias = Some(package.replace('-', "_").to_lowercase());
+            let module = path.with_extension("");
+            let top = module
+                .strip_prefix("src")
+                .ok()
+                .filter(|within| !within.as_os_str().is_empty())
+                .unwrap_or(&module)
+                .components()
+                .next()
+                .and_then(|c| c.as_os_str().to_str().map(ToString::to_string));
+            let mut scan = lock();
+            scan.local.extend(top);
+            scan.files += 1;
+            scan.cached += usize::from(hit);
+            drop(scan);
+            for import in parsed.imports {
+                // The receiver only hangs up if checking failed
+                if tx.send((import, path.clone(), None)).is_err() {
+                    return Quit;
+                }
+            }
+            Continue
+        })
+    });
+    Ok(scan.into_inner().unwrap_or_else(PoisonError::into_inner))
+}
+
+/// Find every project under the workspace `root`, i.e. the directories holding a
+/// pyproject.toml, skipping ignored and hidden directories.
+fn discover_projects(root: &Path) -> Result<Vec<PathBuf>> {
+    let mut projects = Vec::new();
+    for entry in WalkBuilder::new(root).standard_filters(true).build() {
+        let entry = entry?;
+        if entry.file_name() == PYPROJECT
+            && entry.file_type().is_some_and(|t| t.is_file())
+            && let Some(dir) = entry.path().parent()
+        {
+            projects.push(dir.to_path_buf());
         }
-        if let Some(a) = alias {
-            dependencies.entry(a).or_default().push(package);
+    }
+    projects.sort();
+    Ok(projects)
+}
+
+/// Make `path` relative to `root`, which is better left implicit in output.
+fn relative(root: &Path, path: &Path) -> PathBuf {
+    // Either may have been made relative to the current directory already
+    let implicit = |p: &Path| p.strip_prefix(".").unwrap_or(p).to_path_buf();
+    let (root, path) = (implicit(root), implicit(path));
+    path.strip_prefix(&root)
+        .map_or(path.clone(), Path::to_path_buf)
+}
+
+/// Save the cache of parsed imports, which is only worth a warning if it fails.
+fn save_cache(cache: Cache) {
+    if let Err(e) = cache.save() {
+        warn!("Unable to save the cache: {e:#}");
+    }
+}
+
+/// Which of the project's files are scanned, and which are its package code.
+#[derive(Clone)]
+struct Sources {
+    /// Matches the package code, or `None` if every file is package code.
+    package: Option<Gitignore>,
+    /// Ignores the files which are not scanned.
+    exclude: Override,
+}
+
+impl Sources {
+    /// Build the matchers for `include` and `exclude` globs, relative to the project `root`.
+    fn new(root: &Path, include: &[String], exclude: &[String]) -> Result<Sources> {
+        let package = if include.is_empty() {
+            None
         } else {
-            dependencies.insert(package, vec![]);
+            let mut builder = GitignoreBuilder::new(root);
+            for glob in include {
+                builder.add_line(None, glob)?;
+            }
+            Some(builder.build()?)
+        };
+        let mut builder = OverrideBuilder::new(root);
+        for glob in exclude {
+            builder.add(&format!("!{glob}"))?;
         }
-    });
-    Ok(Some(dependencies))
+        Ok(Sources {
+            package,
+            exclude: builder.build()?,
+        })
+    }
+
+    /// Whether the file at `path`, relative to the project root, can credit main dependencies.
+    ///
+    /// Without package globs, every file can, tests included. Files outside the project, e.g.
+    /// under a scan root elsewhere, never match its package globs.
+    fn is_package_code(&self, path: &Path) -> bool {
+        self.package.as_ref().is_none_or(|package| {
+            let path = path.strip_prefix(package.path()).unwrap_or(path);
+            !path.has_root()
+                && !path.starts_with("..")
+                && package.matched_path_or_any_parents(path, false).is_ignore()
+        })
+    }
 }
 
-// Read lines from ignorefile. Ignore empty lines and comments.
-fn read_lines(file: &File) -> io::Result<Vec<String>> {
-    let lines: Vec<_> = BufReader::new(file).lines().collect::<Result<_, _>>()?;
-    Ok(lines
-        .into_iter()
-        .filter(|line| !(line.is_empty() || line.trim_start().starts_with('#')))
-        .collect())
+/// Whether a project file only holds tests, e.g. `tests/test_app.py` or `conftest.py`.
+fn is_test_file(path: &Path) -> bool {
+    let in_tests = path
+        .components()
+        .any(|c| matches!(c.as_os_str().to_str(), Some("test" | "tests")));
+    let stem = path.file_stem().and_then(|stem| stem.to_str());
+    in_tests
+        || stem.is_some_and(|stem| {
+            stem.starts_with("test_") || stem.ends_with("_test") || stem == "conftest"
+        })
 }
 
-// Filter out dependencies from udeps if they are in the ignorefile.
-fn apply_ignorefile(udeps: Vec<String>) -> io::Result<Vec<String>> {
-    let ignore_packages = match File::open(IGNORE_FILE) {
-        Ok(poetryudepsignore) => read_lines(&poetryudepsignore)?,
-        Err(_) => return Ok(udeps),
-    };
+/// Find every dependency needed by `roots`, following the imports dependencies make of others.
+fn needed_by(
+    roots: &BTreeSet<NormalizedName>,
+    imported_by: &BTreeMap<NormalizedName, BTreeSet<NormalizedName>>,
+) -> BTreeSet<NormalizedName> {
+    let mut needed = roots.clone();
+    let mut stack: Vec<NormalizedName> = roots.iter().cloned().collect();
+    while let Some(package) = stack.pop() {
+        for dep in imported_by.get(&package).into_iter().flatten() {
+            if needed.insert(dep.clone()) {
+                debug!(found = %dep, required_by = %package);
+                stack.push(dep.clone());
+            }
+        }
+    }
+    needed
+}
 
-    debug!(ignored = ?ignore_packages);
-    Ok(udeps
-        .into_iter()
-        .filter(|dep| !ignore_packages.contains(dep))
-        .collect())
+/// The dependency groups checked for unused dependencies.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+enum Groups {
+    /// Only the main dependencies.
+    #[default]
+    Main,
+    /// Every group.
+    All,
+    /// The named groups.
+    Only(BTreeSet<String>),
 }
 
-#[allow(clippy::too_many_lines)]
-#[allow(clippy::missing_errors_doc)]
-#[allow(clippy::missing_panics_doc)]
-pub fn run(cli: &Cli) -> Result<Option<Vec<String>>> {
-    let pyproject_path = Path::new("pyproject.toml");
+impl Groups {
+    fn contains(&self, group: &str) -> bool {
+        match self {
+            Groups::Main => group == manifest::MAIN_GROUP,
+            Groups::All => true,
+            Groups::Only(groups) => groups.contains(group),
+        }
+    }
+}
 
-    match pyproject_path.try_exists() {
-        Ok(true) => (),
-        Ok(false) => {
-            error!("pyproject.toml not found. Are you in the root directory of your project?",);
-            // Just fall through, the subsequent read will raise the error for us
+/// Finds the unused dependencies of a Python project.
+///
+/// This is the library interface to everything the `poetry-udeps` binary does. Configure it with
+/// the builder methods, then call [`Analyzer::run`]:
+///
+/// ```no_run
+/// use poetry_udeps::Analyzer;
+///
+/// let report = Analyzer::new("path/to/project")
+///     .groups(["main", "test"])
+///     .mapping("Pillow", "PIL")
+///     .run()?;
+/// for finding in report.unused() {
+///     println!("{finding}");
+/// }
+/// # anyhow::Ok(())
+/// ```
+#[derive(Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Analyzer {
+    root: PathBuf,
+    manifest: ManifestKind,
+    /// The one requirements file to read, instead of every requirements*.txt in `root`.
+    requirements_file: Option<PathBuf>,
+    groups: Groups,
+    scan_roots: Vec<PathBuf>,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    mappings: BTreeMap<NormalizedName, Vec<String>>,
+    virtualenv: bool,
+    lock: bool,
+    strict: bool,
+    no_ignore: bool,
+    workspace: bool,
+    cache_dir: Option<PathBuf>,
+    /// The directories of nested projects, whose files are not the project's own.
+    excluded: Vec<PathBuf>,
+    /// The contents of files with unsaved changes in an editor, which are checked instead of the
+    /// files on disk. Only pyproject.toml and Python files are read from here.
+    unsaved: HashMap<PathBuf, String>,
+}
+
+impl Analyzer {
+    /// Analyze the project in `root`, which holds its manifest.
+    ///
+    /// By default, the manifest is detected, only the main dependencies are checked, and every
+    /// Python file under `root` is scanned.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Analyzer {
+            root: root.into(),
+            manifest: ManifestKind::default(),
+            requirements_file: None,
+            groups: Groups::default(),
+            scan_roots: Vec::new(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            mappings: BTreeMap::new(),
+            virtualenv: false,
+            lock: false,
+            strict: false,
+            no_ignore: false,
+            workspace: false,
+            cache_dir: None,
+            excluded: Vec::new(),
+            unsaved: HashMap::new(),
         }
-        Err(e) => {
-            error!("pyproject.toml not found. Are you in the root directory of your project?",);
-            return Err(e.into());
+    }
+
+    /// Read the dependencies from this kind of manifest.
+    #[must_use]
+    pub fn manifest(mut self, kind: ManifestKind) -> Self {
+        self.manifest = kind;
+        self
+    }
+
+    /// Read the dependencies only from this requirements file in the project, e.g.
+    /// requirements-dev.txt, rather than from every requirements*.txt file.
+    ///
+    /// This implies [`ManifestKind::Requirements`].
+    #[must_use]
+    pub fn requirements_file(mut self, file: impl Into<PathBuf>) -> Self {
+        self.manifest = ManifestKind::Requirements;
+        self.requirements_file = Some(file.into());
+        self
+    }
+
+    /// Check every dependency group, rather than only the main dependencies.
+    #[must_use]
+    pub fn dev(mut self, dev: bool) -> Self {
+        self.groups = if dev { Groups::All } else { Groups::Main };
+        self
+    }
+
+    /// Only check the dependency groups named, e.g. `["main", "test"]`.
+    #[must_use]
+    pub fn groups(mut self, groups: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.groups = Groups::Only(groups.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Scan this directory for imports, instead of the whole project.
+    ///
+    /// May be given more than once. Relative paths are resolved against the project root.
+    #[must_use]
+    pub fn scan_root(mut self, path: impl AsRef<Path>) -> Self {
+        self.scan_roots.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Only count files matching this glob as package code, which can credit main dependencies.
+    ///
+    /// Globs follow the `.gitignore` syntax, relative to the project root, and may be given more
+    /// than once. Other files, like tests, are still scanned, but only credit dev dependencies. By
+    /// default, the globs are `include` in `[tool.poetry-udeps]`, or else the packages configured
+    /// in `tool.poetry.packages`, `tool.setuptools.packages` or Hatch's wheel target. Without any
+    /// globs, every file is package code.
+    #[must_use]
+    pub fn include(mut self, glob: impl Into<String>) -> Self {
+        self.include.push(glob.into());
+        self
+    }
+
+    /// Do not scan files matching this glob, in addition to `exclude` in `[tool.poetry-udeps]`.
+    ///
+    /// Globs follow the `.gitignore` syntax, relative to the project root, and may be given more
+    /// than once.
+    #[must_use]
+    pub fn exclude(mut self, glob: impl Into<String>) -> Self {
+        self.exclude.push(glob.into());
+        self
+    }
+
+    /// Declare that the distribution `package` provides the module `module`.
+    ///
+    /// May be given more than once for the same package. The modules given replace every module
+    /// the package was otherwise thought to provide.
+    #[must_use]
+    pub fn mapping(mut self, package: &str, module: impl Into<String>) -> Self {
+        self.mappings
+            .entry(NormalizedName::new(package))
+            .or_default()
+            .push(module.into());
+        self
+    }
+
+    /// Look for dependency usage in the Poetry virtualenv, as with `--virtualenv`.
+    #[must_use]
+    pub fn virtualenv(mut self, virtualenv: bool) -> Self {
+        self.virtualenv = virtualenv;
+        self
+    }
+
+    /// Use poetry.lock to find dependencies required by other used dependencies, as with
+    /// `--lock`.
+    #[must_use]
+    pub fn lock(mut self, lock: bool) -> Self {
+        self.lock = lock;
+        self
+    }
+
+    /// Fail if a project file cannot be read, decoded or parsed, instead of warning about it.
+    #[must_use]
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Report every dependency, ignoring neither the packages in .poetryudepsignore nor those with
+    /// a `# poetry-udeps: ignore` comment.
+    #[must_use]
+    pub fn no_ignore(mut self, no_ignore: bool) -> Self {
+        self.no_ignore = no_ignore;
+        self
+    }
+
+    /// Cache the imports parsed from each file in `dir`, so that only changed files are reparsed
+    /// by later runs.
+    #[must_use]
+    pub fn cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(dir.into());
+        self
+    }
+
+    /// Treat `root` as a workspace, checking every project under it which has a pyproject.toml.
+    ///
+    /// Each project is checked with its own manifest and .poetryudepsignore, and only its own
+    /// files are scanned: those of a nested project belong to that project. Scan roots are
+    /// resolved against each project.
+    #[must_use]
+    pub fn workspace(mut self, workspace: bool) -> Self {
+        self.workspace = workspace;
+        self
+    }
+
+    /// Fail unless a single project is being analyzed.
+    fn single_project(&self) -> Result<()> {
+        if self.workspace {
+            bail!("this only works for a single project, not a workspace");
         }
+        Ok(())
     }
 
-    let mut main_deps = get_dependencies(pyproject_path, &DepType::Main)?.unwrap();
-    info!(?main_deps);
-    let mut dev_deps = get_dependencies(pyproject_path, &DepType::Dev)?.unwrap_or_default();
-    info!(?dev_deps);
+    /// The path of a file in the project root.
+    fn path(&self, file: &str) -> PathBuf {
+        relative(Path::new("."), &self.root.join(file))
+    }
 
-    let (tx, rx) = flume::bounded::<(ImportStatement, PathBuf)>(100);
+    /// Read the dependencies declared in the project's manifest.
+    fn read_manifest(&self) -> Result<Manifest> {
+        match &self.requirements_file {
+            Some(file) => manifest::read_requirements(&self.root.join(file)),
+            None => manifest::read(&self.root, self.manifest, self.unsaved_pyproject()),
+        }
+    }
 
-    // Setup main thread for stdout
-    let check_dev_deps = cli.dev;
-    let stdout_thread = thread::spawn(move || -> io::Result<Option<Vec<String>>> {
-        for (import, path) in rx {
-            debug!(
-                package = import.package,
-                module = import.module,
-                path = path.to_str(),
-                "Checking import",
-            );
-            // Packages may have several aliases
-            let mut aliases = vec![];
-            if !import.module.is_empty() {
-                // Google-style package naming
-                aliases.push(format!(
-                    "{}-{}",
-                    import.package.replace('.', "-"),
-                    import.module
-                ));
-            }
-            // DBT Adapters
-            if import.package.starts_with("dbt.adapters") {
-                aliases.push({
-                    let parts: Vec<&str> = import.package.split('.').collect();
-                    [parts[0], parts[2]].join("-")
-                });
-            }
-            // SQLAlchemy Extentions
-            if import.package.contains('.') {
-                aliases.push(import.package.split('.').collect::<Vec<&str>>().join("-"));
+    /// Read the project's dependencies, and with `--virtualenv` the files they installed.
+    fn load_project(&self) -> Result<Project> {
+        if self.manifest == ManifestKind::Pyproject {
+            let pyproject_path = self.root.join(PYPROJECT);
+
+            match pyproject_path.try_exists() {
+                Ok(true) => (),
+                Ok(false) => {
+                    error!(
+                        "pyproject.toml not found in {}. Is it the root directory of your project?",
+                        self.root.display()
+                    );
+                    // Just fall through, the subsequent read will raise the error for us
+                }
+                Err(e) => {
+                    error!(
+                        "pyproject.toml not found in {}. Is it the root directory of your project?",
+                        self.root.display()
+                    );
+                    return Err(e.into());
+                }
             }
-            if let Some(p) = import.package.split_once('.') {
-                aliases.push(p.0.to_string());
+        }
+
+        let mut manifest = self.read_manifest()?;
+        for req in manifest.groups.values_mut().flatten() {
+            if let Some(location) = &mut req.location {
+                location.file = relative(&self.root, &location.file);
             }
+        }
+        let mut main_deps = get_dependencies(&manifest, &DepType::Main)?.unwrap();
+        let mut dev_deps = get_dependencies(&manifest, &DepType::Dev)?.unwrap_or_default();
 
-            // Include parent packages after 1 level deep.
-            // This is to catch things like
-            // `from google.auth.transport import requests` --> google-auth
-            let v: Vec<&str> = import.package.split('.').collect();
-            if v.len() >= 2 {
-                aliases.push(format!("{}-{}", v[0], v[1]));
+        let mut files = Vec::new();
+        if self.virtualenv {
+            let declared: BTreeSet<NormalizedName> = main_deps
+                .declared
+                .keys()
+                .chain(dev_deps.declared.keys())
+                .cloned()
+                .collect();
+            let venv_path = get_venv_path(&self.root)?;
+            info!("Reading dependency files in venv: {}", venv_path);
+            for dist in venv::find_distributions(Path::new(&venv_path), &declared)? {
+                // The modules a distribution really provides beat any guess
+                for module in &dist.modules {
+                    main_deps.add_alias(&dist.name, module, AliasSource::Metadata);
+                    dev_deps.add_alias(&dist.name, module, AliasSource::Metadata);
+                }
+                files.extend(dist.files.into_iter().map(|f| (f, dist.name.clone())));
             }
+        }
+        // The user knows best
+        for (package, modules) in &self.mappings {
+            main_deps.override_aliases(package, modules);
+            dev_deps.override_aliases(package, modules);
+        }
 
-            // Just the package
-            aliases.push(import.package);
+        Ok(Project {
+            manifest,
+            main_deps,
+            dev_deps,
+            files,
+        })
+    }
 
-            for alias in aliases {
-                if main_deps.contains_key(&alias) {
-                    if let Some(v) = main_deps.remove(&alias) {
-                        if v.is_empty() {
-                            info!(found = alias, path = path.to_str());
-                        } else {
-                            for orig in v {
-                                info!(found = orig, path = path.to_str());
-                                main_deps.remove(&orig);
+    /// The unsaved contents of the project's pyproject.toml, if any.
+    fn unsaved_pyproject(&self) -> Option<&str> {
+        self.unsaved
+            .get(&self.root.join(PYPROJECT))
+            .map(String::as_str)
+    }
+
+    /// Open the cache of parsed imports, if enabled.
+    fn open_cache(&self) -> Cache {
+        let cache = match &self.cache_dir {
+            Some(dir) => Cache::open(dir, &self.root),
+            None => Cache::disabled(),
+        };
+        cache.with_unsaved(self.unsaved.clone())
+    }
+
+    /// Decide which of the project's files are scanned, and which are its package code.
+    ///
+    /// Globs given to [`Analyzer::include`] replace those configured in pyproject.toml, which
+    /// replace the packages configured for the build tool. Globs given to [`Analyzer::exclude`]
+    /// add to those configured.
+    fn sources(&self) -> Result<Sources> {
+        let config = Config::read(&self.root, self.unsaved_pyproject())?;
+        let include = if !self.include.is_empty() {
+            self.include.clone()
+        } else if !config.include.is_empty() {
+            config.include
+        } else {
+            source_tree::package_globs(&self.root, self.unsaved_pyproject())
+        };
+        let exclude = [config.exclude, self.exclude.clone()].concat();
+        debug!(?include, ?exclude, "Selected sources");
+        Sources::new(&self.root, &include, &exclude)
+    }
+
+    /// Parse every Python file in the project, sending its imports.
+    fn scan_project(
+        &self,
+        sources: &Sources,
+        cache: &Cache,
+        tx: &flume::Sender<Import>,
+    ) -> Result<Scan> {
+        let roots = if self.scan_roots.is_empty() {
+            vec![self.root.clone()]
+        } else {
+            self.scan_roots.iter().map(|r| self.root.join(r)).collect()
+        };
+        scan_project(&self.root, &roots, &self.excluded, sources, cache, tx)
+    }
+
+    /// Check the project's dependencies, returning what was found.
+    #[allow(clippy::missing_errors_doc)]
+    pub fn run(&self) -> Result<Report> {
+        if self.workspace {
+            return self.run_workspace();
+        }
+        let (report, _) = self.check(false)?;
+        Ok(report)
+    }
+
+    /// Check the project's dependencies, returning what was found, along with every import if
+    /// `keep_imports`.
+    #[allow(clippy::too_many_lines)]
+    fn check(&self, keep_imports: bool) -> Result<(Report, Vec<Import>)> {
+        let start = Instant::now();
+        let Project {
+            manifest,
+            main_deps,
+            dev_deps,
+            files,
+        } = self.load_project()?;
+
+        info!(?main_deps);
+        info!(?dev_deps);
+
+        // Only pyproject.toml and Pipfile are read with warnings, e.g. about Poetry 2 projects
+        // declaring a dependency in only one of `[project]` and `[tool.poetry]`, or malformed
+        // suppression comments
+        let manifest_file = match manifest.kind {
+            ManifestKind::Pipfile => manifest::PIPFILE,
+            _ => PYPROJECT,
+        };
+        let manifest_warnings: Vec<FileWarning> = manifest
+            .warnings
+            .iter()
+            .map(|message| FileWarning::new(manifest_file, message.clone()))
+            .collect();
+
+        let lock_graph = if self.lock {
+            Some(LockGraph::from_path(&self.root.join(LOCK_FILE))?)
+        } else {
+            None
+        };
+        // Dependency -> the extras the manifest requests of it, in any group
+        let mut requested: BTreeMap<NormalizedName, BTreeSet<NormalizedName>> = BTreeMap::new();
+        for req in manifest.groups.values().flatten() {
+            requested
+                .entry(req.normalized.clone())
+                .or_default()
+                .extend(req.extras.iter().cloned());
+        }
+
+        let sources = self.sources()?;
+        let (tx, rx) = flume::bounded::<Import>(100);
+
+        // Setup main thread for stdout
+        let groups = self.groups.clone();
+        let package_code = sources.clone();
+        let ignore_file = self.root.join(IGNORE_FILE);
+        let no_ignore = self.no_ignore;
+        let root = self.root.clone();
+        let stdout_thread = thread::spawn(
+            move || -> io::Result<(Report, Unresolved, Vec<Import>)> {
+                // The dependencies imported by the project's code, and by its tests
+                let mut used_in_code: BTreeSet<NormalizedName> = BTreeSet::new();
+                let mut used_in_tests: BTreeSet<NormalizedName> = BTreeSet::new();
+                // The dependencies imported by package code which is not a test, which dev
+                // dependencies are misplaced in
+                let mut used_outside_tests: BTreeSet<NormalizedName> = BTreeSet::new();
+                // Dependency -> the dependencies its own code imports
+                let mut imported_by: BTreeMap<NormalizedName, BTreeSet<NormalizedName>> =
+                    BTreeMap::new();
+                // Dependency -> the imports crediting it
+                let mut usages: BTreeMap<NormalizedName, Vec<Usage>> = BTreeMap::new();
+                // Imports of third-party modules no dependency provides, and where they were first
+                // seen
+                let mut unresolved = Unresolved::new();
+                let mut imports = 0;
+                let mut kept = Vec::new();
+                for (import, path, owner) in rx {
+                    if keep_imports {
+                        kept.push((import.clone(), path.clone(), owner.clone()));
+                    }
+                    debug!(
+                        package = import.package,
+                        module = import.module,
+                        path = path.to_str(),
+                        owner = owner.as_ref().map(NormalizedName::as_str),
+                        "Checking import",
+                    );
+                    imports += 1;
+                    let credited = credited_by(&import, &[&main_deps, &dev_deps]);
+                    let credited: Vec<&NormalizedName> = credited
+                        .into_iter()
+                        .filter(|p| Some(*p) != owner.as_ref())
+                        .collect();
+                    for package in &credited {
+                        usages
+                            .entry((*package).clone())
+                            .or_default()
+                            .push(Usage::new(
+                                Location {
+                                    line: Some(import.line),
+                                    ..Location::file(&path)
+                                },
+                                import.to_string(),
+                                owner.as_ref().map(ToString::to_string),
+                            ));
+                    }
+                    match &owner {
+                        None => {
+                            if credited.is_empty()
+                                && let Some(top) = import.package.split('.').next()
+                                && !is_stdlib(top)
+                            {
+                                let site = (path.clone(), import.line);
+                                let first =
+                                    unresolved.entry(top.to_string()).or_insert(site.clone());
+                                *first = site.min(first.clone());
+                            }
+                            let code = package_code.is_package_code(&path);
+                            if code && !is_test_file(&path) {
+                                used_outside_tests.extend(credited.iter().copied().cloned());
+                            }
+                            let used = if code {
+                                &mut used_in_code
+                            } else {
+                                &mut used_in_tests
+                            };
+                            for package in credited {
+                                if used.insert(package.clone()) {
+                                    info!(found = %package, path = path.to_str());
+                                }
+                            }
+                        }
+                        Some(owner) => {
+                            for package in credited {
+                                imported_by
+                                    .entry(owner.clone())
+                                    .or_default()
+                                    .insert(package.clone());
                             }
                         }
                     }
                 }
-                if dev_deps.contains_key(&alias) {
-                    if let Some(v) = dev_deps.remove(&alias) {
-                        if v.is_empty() {
-                            info!("Found {} in {}", alias, path.display());
+                // Files are scanned in parallel, so restore a stable order
+                for usages in usages.values_mut() {
+                    usages.sort_by(|a, b| {
+                        (&a.location.file, a.location.line)
+                            .cmp(&(&b.location.file, b.location.line))
+                    });
+                }
+
+                // A dependency is only needed by another dependency if that one is used.
+                let needed_by_code = needed_by(&used_in_code, &imported_by);
+                let used = needed_by(
+                    &used_in_code.union(&used_in_tests).cloned().collect(),
+                    &imported_by,
+                );
+
+                let (lock_chains, code_lock_chains) = lock_graph
+                    .map(|graph| {
+                        let roots = |packages: &BTreeSet<NormalizedName>| {
+                            packages
+                                .iter()
+                                .map(|package| {
+                                    let extras =
+                                        requested.get(package).cloned().unwrap_or_default();
+                                    (package.clone(), extras)
+                                })
+                                .collect::<Vec<_>>()
+                        };
+                        (
+                            graph.requirement_chains(roots(&used)),
+                            graph.requirement_chains(roots(&needed_by_code)),
+                        )
+                    })
+                    .unwrap_or_default();
+                let entries = if no_ignore {
+                    Vec::new()
+                } else {
+                    ignores::read(&ignore_file)?
+                };
+                debug!(ignored = ?entries);
+                let ignored: BTreeSet<NormalizedName> = entries
+                    .iter()
+                    .map(|entry| entry.normalized.clone())
+                    .collect();
+                let mut report = Report::default();
+                report.statistics.imports = imports;
+                report.root = root;
+                let mut check = |deps: &Dependencies, main: bool| {
+                    for package in deps.declared.keys() {
+                        // Only dependencies declared in a group being checked
+                        let Some(declaration) = declaration(&manifest, &groups, package, main)
+                        else {
+                            continue;
+                        };
+                        let listed = entries.iter().find(|entry| entry.normalized == *package);
+                        let suppression = declaration.1.suppression.as_ref().filter(|_| !no_ignore);
+                        let package_usages = usages.get(package).cloned().unwrap_or_default();
+                        report.checked.push(checked(
+                            Some(declaration),
+                            deps,
+                            package,
+                            listed.is_some(),
+                            suppression,
+                            package_usages,
+                        ));
+                        let problem = if used.contains(package) {
+                            let misplaced = if main {
+                                // Only imported by tests
+                                !needed_by_code.contains(package)
+                                    && !code_lock_chains.contains_key(package)
+                            } else {
+                                used_outside_tests.contains(package)
+                                    && !main_deps.declared.contains_key(package)
+                            };
+                            misplaced.then(|| (FindingKind::Misplaced, Vec::new()))
+                        } else if let Some(chain) = lock_chains.get(package) {
+                            let chain: Vec<String> =
+                                chain.iter().map(ToString::to_string).collect();
+                            info!(
+                                %package,
+                                chain = chain.join(" -> "),
+                                "Required by another used dependency"
+                            );
+                            Some((FindingKind::Transitive, chain))
                         } else {
-                            for orig in v {
-                                info!("Found {} in {}", orig, path.display());
-                                main_deps.remove(&orig);
-                            }
+                            Some((FindingKind::Unused, Vec::new()))
+                        };
+                        if let Some(entry) = listed
+                            && problem.is_none()
+                        {
+                            info!(%package, "Ignored, but used");
+                            report.findings.push(stale_ignore(
+                                entry,
+                                Path::new(IGNORE_FILE),
+                                Some(declaration.0),
+                            ));
+                        }
+                        if listed.is_some() {
+                            continue;
+                        }
+                        let Some((kind, required_by)) = problem else {
+                            continue;
+                        };
+                        let finding = finding(Some(declaration), deps, package, kind, required_by);
+                        match suppression {
+                            Some(suppression) => report.suppressed.push(Finding {
+                                suppression: Some(suppression.clone()),
+                                ..finding
+                            }),
+                            None => report.findings.push(finding),
                         }
                     }
+                };
+                check(&main_deps, true);
+                check(&dev_deps, false);
+                // Packages which are neither declared in any group, nor imported without being declared
+                for entry in &entries {
+                    let declared = manifest
+                        .groups
+                        .values()
+                        .flatten()
+                        .any(|req| req.normalized == entry.normalized);
+                    let imported = unresolved
+                        .keys()
+                        .any(|module| NormalizedName::new(module) == entry.normalized);
+                    if !declared && !imported {
+                        info!(package = %entry.normalized, "Ignored, but neither declared nor imported");
+                        report
+                            .findings
+                            .push(stale_ignore(entry, Path::new(IGNORE_FILE), None));
+                    }
                 }
+                unresolved.retain(|module, _| !ignored.contains(&NormalizedName::new(module)));
+                Ok((report, unresolved, kept))
+            },
+        );
+
+        // Only the files installed by declared dependencies are relevant
+        let cache = self.open_cache();
+        let cached = scan_dependency_files(&files, &cache, &tx);
+
+        let scan = self.scan_project(&sources, &cache, &tx);
+
+        drop(tx);
+        save_cache(cache);
+        let Ok(checked) = stdout_thread.join() else {
+            bail!("checking the imports failed unexpectedly");
+        };
+        let (mut report, unresolved, imports) = checked?;
+        let scan = scan?;
+        if self.strict && !scan.warnings.is_empty() {
+            let warnings: Vec<String> = scan.warnings.iter().map(ToString::to_string).collect();
+            bail!(
+                "{} file(s) could not be scanned:\n  {}",
+                warnings.len(),
+                warnings.join("\n  ")
+            );
+        }
+        // Imports of the project's own modules are not missing dependencies
+        for (module, (path, line)) in unresolved {
+            if !scan.local.contains(&module) {
+                report.findings.push(missing(module, &path, line));
             }
         }
+        report.warnings = manifest_warnings;
+        report.warnings.extend(scan.warnings);
+        report.statistics.files = scan.files;
+        report.statistics.dependency_files = files.len();
+        report.statistics.cached = scan.cached + cached;
+        report.statistics.elapsed = start.elapsed();
+        Ok((report, imports))
+    }
 
-        let mut udeps = Vec::new();
-        for (key, value) in &main_deps {
-            // Only print the non-alias names
-            if value.is_empty() {
-                udeps.push(key.to_owned());
-            }
+    /// Serve the project as a language server, reading messages from `input` and writing to
+    /// `output` until the client exits, returning whether it shut the server down first.
+    ///
+    /// Unless the project was given explicitly, it is the root the client initializes the server
+    /// with.
+    #[allow(clippy::missing_errors_doc)]
+    pub fn lsp(self, input: impl BufRead, output: impl Write) -> Result<bool> {
+        lsp::serve(self, input, output)
+    }
+
+    /// Check the project, then check it again whenever its files change.
+    ///
+    /// Every finding is written to `out` first, and then how the findings changed after each
+    /// change, e.g. `requests: now unused`. With a cache, only the files which changed are parsed
+    /// again. A failed check, e.g. of a half-edited pyproject.toml, is written to `out` too. This
+    /// only returns if the first check or writing fails.
+    #[allow(clippy::missing_errors_doc)]
+    pub fn watch(&self, out: &mut impl Write) -> Result<Infallible> {
+        let mut watcher = Watcher::new(&self.root);
+        let mut report = self.run()?;
+        for finding in &report.findings {
+            writeln!(out, "{finding}")?;
+        }
+        for warning in &report.warnings {
+            writeln!(out, "warning: {warning}")?;
         }
-        if check_dev_deps {
-            for (key, value) in &dev_deps {
-                // Only print the non-alias names
-                if value.is_empty() {
-                    udeps.push(key.to_owned());
+        loop {
+            out.flush()?;
+            let changed = watcher.wait();
+            info!(?changed, "Files changed");
+            let new = match self.run() {
+                Ok(new) => new,
+                Err(e) => {
+                    writeln!(out, "error: {e:#}")?;
+                    continue;
                 }
+            };
+            let lines = watch::diff(&report, &new);
+            if lines.is_empty() {
+                writeln!(
+                    out,
+                    "No change in findings after {} file(s) changed",
+                    changed.len()
+                )?;
+            }
+            for line in lines {
+                writeln!(out, "{line}")?;
             }
+            report = new;
         }
+    }
 
-        if udeps.is_empty() {
-            Ok(None)
-        } else {
-            // Filter out those from ignorefile
-            let filtered = apply_ignorefile(udeps)?;
-            if filtered.is_empty() {
-                Ok(None)
+    /// Check every project in the workspace, combining their reports in project order.
+    ///
+    /// A project which cannot be checked, e.g. because it declares no dependencies, is reported
+    /// as a warning, unless `strict`.
+    fn run_workspace(&self) -> Result<Report> {
+        let start = Instant::now();
+        let projects = discover_projects(&self.root)?;
+        if projects.is_empty() {
+            bail!("no pyproject.toml found in {}", self.root.display());
+        }
+        let mut report = Report::new(&self.root);
+        for project in &projects {
+            let name = relative(&self.root, project);
+            let name = if name.as_os_str().is_empty() {
+                PathBuf::from(".")
             } else {
-                Ok(Some(filtered))
+                name
+            };
+            info!(project = %name.display(), "Checking project");
+            let analyzer = Analyzer {
+                root: project.clone(),
+                workspace: false,
+                excluded: projects
+                    .iter()
+                    .filter(|p| *p != project && p.starts_with(project))
+                    .cloned()
+                    .collect(),
+                ..self.clone()
+            };
+            match analyzer.run() {
+                Ok(project_report) => report.merge(&name, project_report),
+                Err(e) if !self.strict => report.warnings.push(FileWarning::new(
+                    relative(&self.root, &project.join(PYPROJECT)),
+                    format!("{e:#}"),
+                )),
+                Err(e) => return Err(e.context(format!("checking {}", name.display()))),
             }
         }
-    });
+        report.statistics.elapsed = start.elapsed();
+        Ok(report)
+    }
 
-    if cli.virtualenv {
-        // Iterate over Python files in parallel in the venv
-        let venv_path = get_venv_path()?;
-        info!("Reading files in venv: {}", venv_path);
-        let types = TypesBuilder::new().add_defaults().select("py").build()?;
-        let walker = WalkBuilder::new(venv_path)
-            .standard_filters(false)
-            .types(types)
-            .build_parallel();
-        walker.run(|| {
-            let tx = tx.clone();
-            Box::new(move |result| {
-                use ignore::WalkState::Continue;
-
-                if let Ok(dir) = result {
-                    if dir.file_type().unwrap().is_file() {
-                        let mut file = File::open(dir.path()).unwrap();
-                        let mut buf = Vec::new();
-                        file.read_to_end(&mut buf).unwrap();
-                        let contents = String::from_utf8_lossy(&buf);
-                        let v = parse_python_file(&contents).unwrap();
-
-                        let path = dir.into_path();
-                        for import in v {
-                            tx.send((import, path.clone())).unwrap();
-                        }
-                    }
-                }
+    /// Remove the unused dependencies `udeps` reported by [`Analyzer::run`] from pyproject.toml.
+    ///
+    /// With `dry_run`, nothing is written.
+    #[allow(clippy::missing_errors_doc)]
+    pub fn fix(&self, udeps: &[String], dry_run: bool) -> Result<Fix> {
+        self.single_project()?;
+        if self.read_manifest()?.kind != ManifestKind::Pyproject {
+            bail!("--fix only supports dependencies declared in pyproject.toml");
+        }
+        let dev = self.groups != Groups::Main;
+        let fix = fix::fix(&self.path(PYPROJECT), udeps, dev, dry_run)?;
+        if !dry_run && !fix.removed.is_empty() {
+            fix::offer_lock_refresh(&self.path(LOCK_FILE))?;
+        }
+        Ok(fix)
+    }
 
-                Continue
-            })
-        });
+    /// Remove the stale entries of .poetryudepsignore reported by [`Analyzer::run`] from the
+    /// file, and their findings from `report`, returning the packages removed.
+    #[allow(clippy::missing_errors_doc)]
+    pub fn prune_ignore(&self, report: &mut Report) -> Result<Vec<String>> {
+        self.single_project()?;
+        let stale: Vec<Finding> = report
+            .findings
+            .extract_if(.., |finding| finding.kind == FindingKind::StaleIgnore)
+            .collect();
+        let lines: BTreeSet<usize> = stale
+            .iter()
+            .filter_map(|finding| finding.location.as_ref()?.line)
+            .collect();
+        if !lines.is_empty() {
+            let path = self.path(IGNORE_FILE);
+            ignores::prune_file(&path, &lines)
+                .with_context(|| format!("failed to write {}", path.display()))?;
+        }
+        Ok(stale.into_iter().map(|finding| finding.package).collect())
     }
 
-    // Iterate over Python files in parallel in the current directory
-    let types = TypesBuilder::new().add_defaults().select("py").build()?;
-    let walker = WalkBuilder::new("./")
-        .standard_filters(true)
-        .types(types)
-        .build_parallel();
-    walker.run(|| {
-        let tx = tx.clone();
-        Box::new(move |result| {
-            use ignore::WalkState::Continue;
+    /// Check every group of the project, ignoring nothing, so each declared dependency is
+    /// classified as [`Analyzer::run`] would, and keep every import found on the way.
+    fn check_everything(&self) -> Result<(Report, Vec<Import>)> {
+        Analyzer {
+            groups: Groups::All,
+            no_ignore: true,
+            ..self.clone()
+        }
+        .check(true)
+    }
 
-            if let Ok(dir) = result {
-                if dir.file_type().unwrap().is_file() {
-                    let contents = fs::read_to_string(dir.path()).unwrap();
-                    let v = parse_python_file(&contents).unwrap();
+    /// Explain why `package` is considered used or unused.
+    #[allow(clippy::missing_errors_doc)]
+    pub fn explain(&self, package: &str) -> Result<Explanation> {
+        self.single_project()?;
+        let project = self.load_project()?;
+        let ignored = !self.no_ignore
+            && ignored_packages(&self.path(IGNORE_FILE))?.contains(&NormalizedName::new(package));
+        let (report, imports) = self.check_everything()?;
+        let mut explanation = why::explain(&project, package, ignored, &imports, &report)?;
+        if self.no_ignore {
+            explanation.suppression = None;
+        }
+        Ok(explanation)
+    }
 
-                    let path = dir.into_path();
-                    for import in v {
-                        tx.send((import, path.clone())).unwrap();
-                    }
-                }
+    /// Review the unused dependencies `udeps` reported by [`Analyzer::run`] one at a time.
+    ///
+    /// For each, the user chooses to remove it from pyproject.toml, add it to
+    /// .poetryudepsignore, or skip it. The choices are applied once every dependency has been
+    /// reviewed.
+    #[allow(clippy::missing_errors_doc)]
+    pub fn review(&self, udeps: &[String]) -> Result<Review> {
+        self.single_project()?;
+        if !io::stdin().is_terminal() {
+            bail!("--interactive needs a terminal");
+        }
+        let project = self.load_project()?;
+        let (report, imports) = self.check_everything()?;
+        let extras = review::extras(&self.path(PYPROJECT));
+        let findings = udeps
+            .iter()
+            .map(|dep| {
+                let explanation = why::explain(&project, dep, false, &imports, &report)?;
+                Ok(review::Finding::new(&project, explanation, &extras))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let choices = review::prompt(&findings, &mut io::stdin().lock(), &mut io::stderr())?;
+        let mut result = Review::default();
+        let mut remove = Vec::new();
+        let mut ignore = Vec::new();
+        for (finding, choice) in findings.iter().zip(choices) {
+            let package = finding.explanation.package.clone();
+            match choice {
+                Choice::Remove => remove.push(package),
+                Choice::Ignore(reason) => ignore.push((package, reason)),
+                Choice::Skip => result.skipped.push(package),
             }
+        }
 
-            Continue
-        })
-    });
+        if !remove.is_empty() {
+            let fix = self.fix(&remove, false)?;
+            result.removed = fix.removed;
+            result.skipped.extend(fix.missing);
+        }
+        if !ignore.is_empty() {
+            review::append_ignores(&self.path(IGNORE_FILE), &ignore)?;
+            result.ignored = ignore.into_iter().map(|(package, _)| package).collect();
+        }
+        Ok(result)
+    }
+}
 
-    drop(tx);
-    match stdout_thread.join() {
-        Ok(j) => {
-            match j {
-                Ok(deps) => Ok(deps),
-                Err(err) => {
-                    // A broken pipe means graceful termination, so fall through.
-                    // Otherwise, something bad happened while writing to stdout, so bubble
-                    // it up.
-                    if err.kind() == io::ErrorKind::BrokenPipe {
-                        Ok(None)
-                    } else {
-                        Err(err.into())
-                    }
-                }
+impl From<&Cli> for Analyzer {
+    fn from(cli: &Cli) -> Self {
+        let (root, kind) = match (&cli.project, &cli.manifest_path) {
+            (_, Some(path)) => {
+                let root = path.parent().filter(|p| !p.as_os_str().is_empty());
+                let kind = match cli.manifest {
+                    ManifestKind::Auto => ManifestKind::from_path(path).unwrap_or_default(),
+                    kind => kind,
+                };
+                (root.unwrap_or(Path::new(".")), kind)
             }
-        }
-        Err(_) => todo!(),
+            (Some(project), None) => (project.as_path(), cli.manifest),
+            (None, None) => (Path::new("."), cli.manifest),
+        };
+        let analyzer = cli
+            .include
+            .iter()
+            .fold(Analyzer::new(root), Analyzer::include)
+            .manifest(kind);
+        // A requirements file is read alone, not along with the others next to it
+        let file = cli
+            .manifest_path
+            .as_deref()
+            .filter(|path| {
+                kind == ManifestKind::Requirements
+                    && ManifestKind::from_path(path) == Some(ManifestKind::Requirements)
+            })
+            .and_then(Path::file_name);
+        let analyzer = match file {
+            Some(file) => analyzer.requirements_file(file),
+            None => analyzer,
+        };
+        let analyzer = cli.exclude.iter().fold(analyzer, Analyzer::exclude);
+        let cache_dir = if cli.no_cache {
+            None
+        } else {
+            cli.cache_dir.clone().or_else(cache::default_dir)
+        };
+        let analyzer = match cache_dir {
+            Some(dir) => analyzer.cache_dir(dir),
+            None => analyzer,
+        };
+        analyzer
+            .dev(cli.dev)
+            .virtualenv(cli.virtualenv)
+            .lock(cli.lock)
+            .strict(cli.strict)
+            .no_ignore(cli.no_ignore)
+            .workspace(cli.workspace)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::{ffi::OsStr, fs};
+
+    fn dependencies(names: &[&str]) -> Dependencies {
+        let mut manifest = Manifest::default();
+        manifest.extend(
+            manifest::MAIN_GROUP,
+            names.iter().copied().map(Requirement::new),
+        );
+        get_dependencies(&manifest, &DepType::Main)
+            .unwrap()
+            .unwrap()
+    }
+
+    /// Write `contents` to the file at `path` under `root`, creating its directories.
+    fn write(root: &Path, path: &str, contents: &str) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    fn credited(import: &str, module: &str, deps: &Dependencies) -> Vec<String> {
+        let import = ImportStatement {
+            package: import.to_string(),
+            module: module.to_string(),
+            line: 1,
+        };
+        credited_by(&import, &[deps])
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_credited_by_namespace_packages() {
+        let deps = dependencies(&[
+            "google-cloud",
+            "google-cloud-storage",
+            "google-cloud-bigquery",
+            "google-cloud-bigquery-storage",
+            "protobuf",
+            "azure-storage-blob",
+            "opentelemetry-api",
+            "opentelemetry-instrumentation-requests",
+            "dbt-postgres",
+        ]);
+        assert_eq!(
+            credited("google.cloud", "storage", &deps),
+            ["google-cloud-storage"]
+        );
+        assert_eq!(
+            credited("google.cloud.bigquery_storage", "BigQueryReadClient", &deps),
+            ["google-cloud-bigquery-storage"]
+        );
+        assert_eq!(
+            credited("google.protobuf", "json_format", &deps),
+            ["protobuf"]
+        );
+        assert_eq!(
+            credited("azure.storage.blob", "BlobClient", &deps),
+            ["azure-storage-blob"]
+        );
+        assert_eq!(
+            credited("opentelemetry.instrumentation.requests", "", &deps),
+            ["opentelemetry-instrumentation-requests"]
+        );
+        assert_eq!(
+            credited("opentelemetry", "trace", &deps),
+            ["opentelemetry-api"]
+        );
+        assert_eq!(
+            credited("dbt.adapters.postgres", "", &deps),
+            ["dbt-postgres"]
+        );
+        assert!(credited("numpy", "", &deps).is_empty());
+    }
+
+    #[test]
+    fn test_credited_by_provided_modules() {
+        let mut deps = dependencies(&["google-cloud-core", "PyYAML"]);
+        deps.add_alias(
+            &NormalizedName::new("google-cloud-core"),
+            "google.cloud.client",
+            AliasSource::Metadata,
+        );
+        deps.add_alias(
+            &NormalizedName::new("numpy"),
+            "numpy",
+            AliasSource::Metadata,
+        );
+        assert_eq!(
+            credited("google.cloud.client", "Client", &deps),
+            ["google-cloud-core"]
+        );
+        assert_eq!(credited("yaml", "", &deps), ["pyyaml"]);
+        assert!(credited("numpy", "", &deps).is_empty());
+    }
+
+    #[test]
+    fn test_analyzer() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(
+            root.path().join(PYPROJECT),
+            r#"[project]
+dependencies = ["requests", "Pillow", "PyYAML"]
+
+[dependency-groups]
+test = ["pytest"]
+lint = ["ruff"]
+"#,
+        )
+        .unwrap();
+        fs::create_dir_all(root.path().join("src/app")).unwrap();
+        fs::create_dir_all(root.path().join("tests")).unwrap();
+        fs::write(
+            root.path().join("src/app/main.py"),
+            "import requests\nfrom PIL import Image\n",
+        )
+        .unwrap();
+        fs::write(root.path().join("tests/test_main.py"), "import pytest\n").unwrap();
+        fs::write(root.path().join("setup.py"), "import yaml\n").unwrap();
+
+        let packages = |report: &Report| -> Vec<String> {
+            report.unused().map(|f| f.package.clone()).collect()
+        };
+        let report = Analyzer::new(root.path()).run().unwrap();
+        assert_eq!(packages(&report), ["Pillow"]);
+        assert_eq!(report.statistics.files, 3);
+        assert_eq!(report.statistics.imports, 4);
+        let requests = &report.checked[2];
+        assert_eq!(requests.package, "requests");
+        assert_eq!(requests.usages[0].location.to_string(), "src/app/main.py:1");
+        assert_eq!(requests.usages[0].statement, "import requests");
+        let location = report.findings[0].location.as_ref().unwrap();
+        assert_eq!(location.to_string(), "pyproject.toml:2:29");
+
+        let report = Analyzer::new(root.path())
+            .groups(["main", "lint"])
+            .mapping("pillow", "PIL")
+            .scan_root("src")
+            .run()
+            .unwrap();
+        assert_eq!(packages(&report), ["PyYAML", "ruff"]);
+        assert_eq!(report.statistics.files, 1);
+        assert_eq!(report.checked.len(), 4);
+
+        let cache = tempfile::tempdir().unwrap();
+        let analyzer = Analyzer::new(root.path()).cache_dir(cache.path());
+        assert_eq!(analyzer.run().unwrap().statistics.cached, 0);
+        let report = analyzer.run().unwrap();
+        assert_eq!(report.statistics.cached, 3);
+        assert_eq!(packages(&report), ["Pillow"]);
+    }
+
+    #[test]
+    fn test_analyzer_from_cli() {
+        let root = tempfile::tempdir().unwrap();
+        let pipfile = root.path().join("Pipfile");
+        fs::write(&pipfile, "[packages]\n").unwrap();
+        let parse = |args: &[&OsStr]| {
+            Cli::try_parse_from([OsStr::new("poetry-udeps")].iter().chain(args))
+                .map(|cli| Analyzer::from(&cli))
+        };
+
+        let analyzer = parse(&[]).unwrap();
+        assert_eq!(analyzer.root, Path::new("."));
+        assert_eq!(analyzer.manifest, ManifestKind::Auto);
+
+        let analyzer = parse(&["--project".as_ref(), root.path().as_ref()]).unwrap();
+        assert_eq!(analyzer.root, root.path());
+
+        let analyzer = parse(&["--manifest-path".as_ref(), pipfile.as_ref()]).unwrap();
+        assert_eq!(analyzer.root, root.path());
+        assert_eq!(analyzer.manifest, ManifestKind::Pipfile);
+
+        let requirements = root.path().join("requirements-dev.txt");
+        fs::write(&requirements, "pytest\n").unwrap();
+        let analyzer = parse(&["--manifest-path".as_ref(), requirements.as_ref()]).unwrap();
+        assert_eq!(analyzer.manifest, ManifestKind::Requirements);
+        assert_eq!(
+            analyzer.requirements_file.as_deref(),
+            Some(Path::new("requirements-dev.txt"))
+        );
+
+        let missing = root.path().join("pyproject.toml");
+        assert!(parse(&["--manifest-path".as_ref(), missing.as_ref()]).is_err());
+        let setup_py = root.path().join("setup.py");
+        assert!(parse(&["--manifest-path".as_ref(), setup_py.as_ref()]).is_err());
+    }
+
+    #[test]
+    fn test_analyzer_workspace() {
+        let root = tempfile::tempdir().unwrap();
+        write(root.path(), PYPROJECT, "[tool.ruff]\nline-length = 100\n");
+        write(
+            root.path(),
+            "api/pyproject.toml",
+            "[project]\ndependencies = [\"requests\", \"PyYAML\"]\n",
+        );
+        write(root.path(), "api/app.py", "import requests\n");
+        write(
+            root.path(),
+            "api/worker/pyproject.toml",
+            "[project]\ndependencies = [\"PyYAML\", \"click\"]\n",
+        );
+        write(
+            root.path(),
+            "api/worker/main.py",
+            "import yaml\nimport click\n",
+        );
+        write(root.path(), "api/worker/.poetryudepsignore", "click\n");
+        write(
+            root.path(),
+            ".venv/pkg/pyproject.toml",
+            "[project]\ndependencies = [\"numpy\"]\n",
+        );
+
+        let report = Analyzer::new(root.path()).workspace(true).run().unwrap();
+        assert_eq!(
+            report.render(Format::Text).unwrap(),
+            "api/pyproject.toml:2:29: PyYAML (group: main) is unused
+api/worker/.poetryudepsignore:1: click is listed in .poetryudepsignore, but is used
+"
+        );
+        let checked: Vec<String> = report
+            .checked
+            .iter()
+            .map(|c| format!("{}: {}", c.project.as_ref().unwrap().display(), c.package))
+            .collect();
+        assert_eq!(
+            checked,
+            [
+                "api: PyYAML",
+                "api: requests",
+                "api/worker: click",
+                "api/worker: PyYAML"
+            ]
+        );
+        let junit = report.render(Format::Junit).unwrap();
+        assert!(
+            junit.contains(
+                "<testsuite name=\"api/worker\" tests=\"2\" failures=\"0\" skipped=\"1\">"
+            )
+        );
+        assert_eq!(report.statistics.files, 2);
+        assert_eq!(report.warnings.len(), 1);
+        assert_eq!(report.warnings[0].path, Path::new(PYPROJECT));
+
+        assert!(
+            Analyzer::new(root.path())
+                .workspace(true)
+                .strict(true)
+                .run()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_analyzer_sources() {
+        let root = tempfile::tempdir().unwrap();
+        write(
+            root.path(),
+            PYPROJECT,
+            r#"[tool.poetry]
+packages = [{ include = "app", from = "src" }]
+
+[tool.poetry.dependencies]
+requests = "*"
+boto3 = "*"
+PyYAML = "*"
+
+[tool.poetry-udeps]
+exclude = ["migrations/"]
+"#,
+        );
+        write(root.path(), "src/app/main.py", "import requests\n");
+        write(root.path(), "scripts/deploy.py", "import boto3\n");
+        write(root.path(), "migrations/0001_initial.py", "import yaml\n");
+
+        let kinds = |report: &Report| -> Vec<String> {
+            report
+                .findings
+                .iter()
+                .map(|f| format!("{}: {}", f.package, f.kind.rule_id()))
+                .collect()
+        };
+        let report = Analyzer::new(root.path()).run().unwrap();
+        assert_eq!(kinds(&report), ["boto3: misplaced", "PyYAML: unused"]);
+        assert_eq!(report.statistics.files, 2);
+
+        let report = Analyzer::new(root.path())
+            .include("/src")
+            .include("/scripts")
+            .exclude("deploy.py")
+            .run()
+            .unwrap();
+        assert_eq!(kinds(&report), ["boto3: unused", "PyYAML: unused"]);
+
+        // Files outside the project are not its package code
+        let shared = tempfile::tempdir().unwrap();
+        fs::write(shared.path().join("config.py"), "import yaml\n").unwrap();
+        let report = Analyzer::new(root.path())
+            .scan_root("src")
+            .scan_root(shared.path())
+            .run()
+            .unwrap();
+        assert_eq!(kinds(&report), ["boto3: unused", "PyYAML: misplaced"]);
+    }
+
+    #[test]
+    fn test_analyzer_tests() {
+        let root = tempfile::tempdir().unwrap();
+        write(
+            root.path(),
+            PYPROJECT,
+            r#"[tool.poetry.dependencies]
+requests = "*"
+PyYAML = "*"
+
+[tool.poetry.group.test.dependencies]
+pytest = "*"
+freezegun = "*"
+"#,
+        );
+        write(
+            root.path(),
+            "mypkg/__init__.py",
+            "import requests\nimport freezegun\n",
+        );
+        write(
+            root.path(),
+            "mypkg/tests/test_config.py",
+            "import yaml\nimport pytest\n",
+        );
+
+        let kinds = |report: &Report| -> Vec<String> {
+            report
+                .findings
+                .iter()
+                .map(|f| format!("{}: {}", f.package, f.kind.rule_id()))
+                .collect()
+        };
+        // Without configured packages, tests credit main dependencies too
+        let analyzer = Analyzer::new(root.path()).groups(["main", "test"]);
+        assert_eq!(kinds(&analyzer.run().unwrap()), ["freezegun: misplaced"]);
+
+        // Tests within the configured packages are package code, but dev dependencies are still
+        // expected there
+        let report = analyzer.include("/mypkg").run().unwrap();
+        assert_eq!(kinds(&report), ["freezegun: misplaced"]);
+    }
+
+    #[test]
+    fn test_analyzer_warnings() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(
+            root.path().join(PYPROJECT),
+            "[project]\ndependencies = [\"requests\"]\n",
+        )
+        .unwrap();
+        fs::write(root.path().join("latin.py"), b"import requests # caf\xe9\n").unwrap();
+
+        let report = Analyzer::new(root.path()).run().unwrap();
+        assert_eq!(report.unused().count(), 0);
+        assert_eq!(report.statistics.files, 1);
+        let warnings: Vec<String> = report.warnings.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            warnings,
+            ["latin.py: invalid UTF-8 at byte 21, so it was decoded lossily"]
+        );
+
+        let error = Analyzer::new(root.path()).strict(true).run().unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .starts_with("1 file(s) could not be scanned")
+        );
+    }
+
+    #[test]
+    fn test_analyzer_missing() {
+        let root = tempfile::tempdir().unwrap();
+        write(
+            root.path(),
+            PYPROJECT,
+            "[project]\ndependencies = [\"requests\"]\n",
+        );
+        write(
+            root.path(),
+            "src/app/__init__.py",
+            "import requests\nimport app.tasks\nimport celery\nimport _pytest\n",
+        );
+        write(root.path(), "src/app/celery.py", "import _thread\n");
+        write(
+            root.path(),
+            "scripts/tool.py",
+            "import app\nimport scripts\nimport numpy\n",
+        );
+
+        let report = Analyzer::new(root.path()).run().unwrap();
+        let missing: Vec<String> = report
+            .findings
+            .iter()
+            .filter(|finding| finding.kind == FindingKind::Missing)
+            .map(ToString::to_string)
+            .collect();
+        // Only top-level modules are the project's own, so `celery` is not app/celery.py
+        assert_eq!(
+            missing,
+            [
+                "src/app/__init__.py:4: _pytest is imported, but not declared as a dependency",
+                "src/app/__init__.py:3: celery is imported, but not declared as a dependency",
+                "scripts/tool.py:3: numpy is imported, but not declared as a dependency",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_analyzer_fix_manifest_kind() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(
+            root.path().join("setup.cfg"),
+            "[options]\ninstall_requires =\n    requests\n",
+        )
+        .unwrap();
+        let error = Analyzer::new(root.path())
+            .fix(&["requests".to_string()], true)
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "--fix only supports dependencies declared in pyproject.toml"
+        );
+    }
+
+    #[test]
+    fn test_analyzer_manifest_warnings() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(
+            root.path().join(PYPROJECT),
+            r#"
+[project]
+dependencies = ["requests"]
+
+[tool.poetry.dependencies]
+python = "^3.12"
+httpx = "^0.27"
+"#,
+        )
+        .unwrap();
+        fs::write(root.path().join("main.py"), "import requests, httpx\n").unwrap();
+
+        let report = Analyzer::new(root.path()).run().unwrap();
+        let warnings: Vec<String> = report.warnings.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            warnings,
+            [
+                "pyproject.toml: requests is declared in project.dependencies but not in tool.poetry.dependencies",
+                "pyproject.toml: httpx is declared in tool.poetry.dependencies but not in project.dependencies",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_analyzer_suppression() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(
+            root.path().join(PYPROJECT),
+            r#"[tool.poetry.dependencies]
+python = "^3.12"
+asyncpg = "^0.29"  # poetry-udeps: ignore (loaded by sqlalchemy)
+PyYAML = "*"  # poetry-udeps: ignore it
+"#,
+        )
+        .unwrap();
+        fs::write(root.path().join("app.py"), "").unwrap();
+
+        let analyzer = Analyzer::new(root.path());
+        let report = analyzer.run().unwrap();
+        let unused: Vec<&str> = report.unused().map(|f| f.package.as_str()).collect();
+        assert_eq!(unused, ["PyYAML"]);
+        let warnings: Vec<String> = report.warnings.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            warnings,
+            [
+                "pyproject.toml: line 4: `# poetry-udeps: ignore it` is not understood, expected `# poetry-udeps: ignore` or `# poetry-udeps: ignore (reason)`"
+            ]
+        );
+        // Suppressed findings only reach the JSON and SARIF reports
+        let [suppressed] = report.suppressed.as_slice() else {
+            panic!("expected one suppressed finding: {:?}", report.suppressed);
+        };
+        assert_eq!(
+            (suppressed.package.as_str(), suppressed.kind),
+            ("asyncpg", FindingKind::Unused)
+        );
+        assert!(
+            report
+                .render(Format::Json)
+                .unwrap()
+                .contains("loaded by sqlalchemy")
+        );
+        assert!(!report.render(Format::Text).unwrap().contains("asyncpg"));
+        let asyncpg = report
+            .checked
+            .iter()
+            .find(|checked| checked.package == "asyncpg")
+            .unwrap();
+        assert!(asyncpg.ignored);
+        assert_eq!(
+            asyncpg.suppression.as_ref().unwrap().reason.as_deref(),
+            Some("loaded by sqlalchemy")
+        );
+        assert!(
+            analyzer
+                .explain("asyncpg")
+                .unwrap()
+                .to_string()
+                .contains("never reported: loaded by sqlalchemy")
+        );
+    }
+
+    #[test]
+    fn test_analyzer_explain_lock() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(
+            root.path().join(PYPROJECT),
+            "[project]\ndependencies = [\"SQLAlchemy\", \"greenlet\"]\n",
+        )
+        .unwrap();
+        fs::write(
+            root.path().join(LOCK_FILE),
+            r#"
+[[package]]
+name = "SQLAlchemy"
+version = "2.0.30"
+
+[package.dependencies]
+greenlet = "!=0.4.17"
+
+[[package]]
+name = "greenlet"
+version = "3.0.3"
+"#,
+        )
+        .unwrap();
+        fs::write(root.path().join("app.py"), "import sqlalchemy\n").unwrap();
+
+        let explanation = Analyzer::new(root.path())
+            .lock(true)
+            .explain("greenlet")
+            .unwrap();
+        assert_eq!(
+            explanation.to_string().lines().nth(1),
+            Some(
+                "It is transitive: greenlet is not imported, but is required by sqlalchemy -> greenlet."
+            )
+        );
+        let explanation = Analyzer::new(root.path()).explain("greenlet").unwrap();
+        assert_eq!(
+            explanation.to_string().lines().nth(1),
+            Some("It is unused: no import in the project credits it.")
+        );
+    }
+
+    #[test]
+    fn test_analyzer_stale_ignores() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(
+            root.path().join(PYPROJECT),
+            "[project]\ndependencies = [\"requests\", \"PyYAML\"]\n",
+        )
+        .unwrap();
+        fs::write(
+            root.path().join("app.py"),
+            "import requests\nimport numpy\n",
+        )
+        .unwrap();
+        let ignore_file = root.path().join(IGNORE_FILE);
+        fs::write(
+            &ignore_file,
+            "requests\n# removed long ago\nflask\n\nnumpy\nPyYAML\n",
+        )
+        .unwrap();
+
+        let analyzer = Analyzer::new(root.path());
+        let mut report = analyzer.run().unwrap();
+        let findings: Vec<String> = report.findings.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            findings,
+            [
+                ".poetryudepsignore:1: requests is listed in .poetryudepsignore, but is used",
+                ".poetryudepsignore:3: flask is listed in .poetryudepsignore, but is neither declared nor imported",
+            ]
+        );
+
+        // The ignore file never hides a problem with --no-ignore
+        let unignored = analyzer.clone().no_ignore(true).run().unwrap();
+        let findings: Vec<(&str, &str)> = unignored
+            .findings
+            .iter()
+            .map(|finding| (finding.package.as_str(), finding.kind.rule_id()))
+            .collect();
+        assert_eq!(findings, [("PyYAML", "unused"), ("numpy", "missing")]);
+
+        assert_eq!(
+            analyzer.prune_ignore(&mut report).unwrap(),
+            ["requests", "flask"]
+        );
+        assert!(report.findings.is_empty());
+        assert_eq!(
+            fs::read_to_string(&ignore_file).unwrap(),
+            "\nnumpy\nPyYAML\n"
+        );
+        assert!(analyzer.run().unwrap().findings.is_empty());
     }
 }
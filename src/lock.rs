@@ -0,0 +1,319 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    fs,
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use toml::Value;
+use tracing::debug;
+
+use crate::name::NormalizedName;
+
+/// Package -> the extras requested of it, for the packages something requires.
+type Requires = BTreeMap<NormalizedName, BTreeSet<NormalizedName>>;
+
+/// The package dependency graph recorded in poetry.lock.
+#[derive(Debug, Default)]
+pub struct LockGraph {
+    /// Package -> the packages it always requires.
+    requires: BTreeMap<NormalizedName, Requires>,
+    /// Package -> extra -> the packages which the extra adds.
+    extras: BTreeMap<NormalizedName, BTreeMap<NormalizedName, Requires>>,
+}
+
+/// The extras requested by a `[package.dependencies]` entry, which is a constraint, a table, or
+/// an array of tables for constraints depending on markers.
+fn requested_extras(spec: &Value) -> BTreeSet<NormalizedName> {
+    let tables = match spec {
+        Value::Array(specs) => specs.iter().collect(),
+        spec => vec![spec],
+    };
+    tables
+        .into_iter()
+        .filter_map(|spec| spec.get("extras").and_then(Value::as_array))
+        .flatten()
+        .filter_map(Value::as_str)
+        .map(NormalizedName::new)
+        .collect()
+}
+
+/// Whether a `[package.dependencies]` entry is only installed for an extra.
+fn is_optional(spec: &Value) -> bool {
+    let tables = match spec {
+        Value::Array(specs) => specs.iter().collect(),
+        spec => vec![spec],
+    };
+    tables
+        .into_iter()
+        .any(|spec| spec.get("optional").and_then(Value::as_bool) == Some(true))
+}
+
+impl LockGraph {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let lock = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Self::parse(&lock)
+    }
+
+    /// Parse the `[[package]]` entries of a poetry.lock file.
+    ///
+    /// Optional dependencies are only followed for the extras which are requested, e.g. by
+    /// `sqlalchemy = { extras = ["asyncio"] }` in the manifest, or by another locked package.
+    pub fn parse(lock: &str) -> Result<Self> {
+        let value = lock
+            .parse::<Value>()
+            .context("failed to parse poetry.lock")?;
+        let mut graph = LockGraph::default();
+        for package in value
+            .get("package")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let Some(name) = package.get("name").and_then(Value::as_str) else {
+                continue;
+            };
+            let name = NormalizedName::new(name);
+            let deps = package.get("dependencies").and_then(Value::as_table);
+            let requires = graph.requires.entry(name.clone()).or_default();
+            for (dep, spec) in deps.into_iter().flatten() {
+                if !is_optional(spec) {
+                    requires.insert(NormalizedName::new(dep), requested_extras(spec));
+                }
+            }
+            // Extras are lists of requirements, e.g. "asyncpg (>=0.29)"
+            for (extra, reqs) in package
+                .get("extras")
+                .and_then(Value::as_table)
+                .into_iter()
+                .flatten()
+            {
+                let adds = graph
+                    .extras
+                    .entry(name.clone())
+                    .or_default()
+                    .entry(NormalizedName::new(extra))
+                    .or_default();
+                for req in reqs
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Value::as_str)
+                {
+                    let dep = req
+                        .split(|c: char| c.is_whitespace() || "[([;<>=!~".contains(c))
+                        .next()
+                        .unwrap_or(req);
+                    if dep.is_empty() {
+                        continue;
+                    }
+                    let extras = deps
+                        .and_then(|deps| {
+                            deps.iter().find(|(name, _)| {
+                                NormalizedName::new(name) == NormalizedName::new(dep)
+                            })
+                        })
+                        .map(|(_, spec)| requested_extras(spec))
+                        .unwrap_or_default();
+                    adds.insert(NormalizedName::new(dep), extras);
+                }
+            }
+        }
+        debug!(packages = graph.requires.len(), "Parsed poetry.lock");
+        Ok(graph)
+    }
+
+    /// The packages `package` requires with `extras`, and the extras requested of each.
+    fn requirements<'a>(
+        &'a self,
+        package: &NormalizedName,
+        extras: &'a BTreeSet<NormalizedName>,
+    ) -> impl Iterator<Item = (&'a NormalizedName, &'a BTreeSet<NormalizedName>)> {
+        let optional = self.extras.get(package);
+        self.requires.get(package).into_iter().flatten().chain(
+            extras
+                .iter()
+                .filter_map(move |extra| optional?.get(extra))
+                .flatten(),
+        )
+    }
+
+    /// Find every package required, directly or transitively, by the `roots`, each with the
+    /// extras requested of it.
+    ///
+    /// Each reachable package maps to the shortest chain of packages which requires it, starting
+    /// at one of the roots and ending with the package itself. The roots themselves are never
+    /// included, even when another root requires them.
+    pub fn requirement_chains(
+        &self,
+        roots: impl IntoIterator<Item = (NormalizedName, BTreeSet<NormalizedName>)>,
+    ) -> BTreeMap<NormalizedName, Vec<NormalizedName>> {
+        let mut parent: BTreeMap<NormalizedName, Option<NormalizedName>> = BTreeMap::new();
+        // Package -> the extras requested of it so far
+        let mut enabled: Requires = BTreeMap::new();
+        let mut queue = VecDeque::new();
+        for (root, extras) in roots {
+            parent.entry(root.clone()).or_insert(None);
+            enabled.entry(root.clone()).or_default().extend(extras);
+            queue.push_back(root);
+        }
+
+        let mut chains = BTreeMap::new();
+        while let Some(package) = queue.pop_front() {
+            let extras = enabled.get(&package).cloned().unwrap_or_default();
+            for (dep, dep_extras) in self.requirements(&package, &extras) {
+                if parent.contains_key(dep) {
+                    // Expand the package again if this requests more of its extras
+                    let known = enabled.entry(dep.clone()).or_default();
+                    if !dep_extras.is_subset(known) {
+                        known.extend(dep_extras.iter().cloned());
+                        queue.push_back(dep.clone());
+                    }
+                    continue;
+                }
+                parent.insert(dep.clone(), Some(package.clone()));
+                enabled.insert(dep.clone(), dep_extras.clone());
+                queue.push_back(dep.clone());
+
+                let mut chain = vec![dep.clone()];
+                let mut current = Some(package.clone());
+                while let Some(p) = current {
+                    current = parent.get(&p).cloned().flatten();
+                    chain.push(p);
+                }
+                chain.reverse();
+                chains.insert(dep.clone(), chain);
+            }
+        }
+        chains
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const LOCK: &str = r#"
+[[package]]
+name = "SQLAlchemy"
+version = "2.0.30"
+optional = false
+
+[package.dependencies]
+asyncpg = {version = "*", optional = true}
+greenlet = {version = "!=0.4.17", markers = "platform_machine == \"x86_64\""}
+typing-extensions = ">=4.6.0"
+
+[package.extras]
+postgresql-asyncpg = ["asyncpg", "greenlet (!=0.4.17)"]
+
+[[package]]
+name = "asyncpg"
+version = "0.29.0"
+
+[[package]]
+name = "greenlet"
+version = "3.0.3"
+
+[[package]]
+name = "typing_extensions"
+version = "4.12.0"
+
+[[package]]
+name = "requests"
+version = "2.32.0"
+
+[package.dependencies]
+urllib3 = ">=1.21.1,<3"
+
+[[package]]
+name = "urllib3"
+version = "2.2.1"
+
+[[package]]
+name = "httpx"
+version = "0.27.0"
+
+[package.dependencies]
+httpcore = {version = "==1.*", extras = ["http2"]}
+
+[[package]]
+name = "httpcore"
+version = "1.0.5"
+
+[package.dependencies]
+h2 = {version = ">=3,<5", optional = true}
+
+[package.extras]
+http2 = ["h2 (>=3,<5)"]
+
+[[package]]
+name = "h2"
+version = "4.1.0"
+
+[metadata]
+lock-version = "2.0"
+"#;
+
+    #[test]
+    fn test_parse() {
+        let graph = LockGraph::parse(LOCK).unwrap();
+        let names = |requires: &Requires| -> Vec<String> {
+            requires.keys().map(ToString::to_string).collect()
+        };
+        assert_eq!(
+            names(&graph.requires["sqlalchemy"]),
+            ["greenlet", "typing-extensions"]
+        );
+        assert_eq!(
+            names(&graph.extras["sqlalchemy"]["postgresql-asyncpg"]),
+            ["asyncpg", "greenlet"]
+        );
+        assert_eq!(
+            graph.requires["httpx"][&NormalizedName::new("httpcore")],
+            BTreeSet::from([NormalizedName::new("http2")])
+        );
+        assert!(graph.requires["asyncpg"].is_empty());
+    }
+
+    #[test]
+    fn test_requirement_chains() {
+        let graph = LockGraph::parse(LOCK).unwrap();
+        let chains = |roots: &[(&str, &[&str])]| {
+            graph.requirement_chains(roots.iter().map(|(root, extras)| {
+                (
+                    NormalizedName::new(root),
+                    extras
+                        .iter()
+                        .map(|extra| NormalizedName::new(extra))
+                        .collect(),
+                )
+            }))
+        };
+        let chain = |chains: &BTreeMap<NormalizedName, Vec<NormalizedName>>, name: &str| {
+            chains[name]
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        let plain = chains(&[("SQLAlchemy", &[])]);
+        assert_eq!(
+            chain(&plain, "typing-extensions"),
+            ["sqlalchemy", "typing-extensions"]
+        );
+        // Optional dependencies are only followed for the extras requested
+        assert!(!plain.contains_key("asyncpg"));
+        assert!(!plain.contains_key("urllib3"));
+        assert!(!plain.contains_key("sqlalchemy"));
+
+        let asyncpg = chains(&[("SQLAlchemy", &["postgresql_asyncpg"])]);
+        assert_eq!(chain(&asyncpg, "asyncpg"), ["sqlalchemy", "asyncpg"]);
+
+        // Extras requested by a locked package are followed too
+        let httpx = chains(&[("httpx", &[])]);
+        assert_eq!(chain(&httpx, "h2"), ["httpx", "httpcore", "h2"]);
+    }
+}
@@ -0,0 +1,765 @@
+//! A language server publishing the findings as diagnostics, for editors to underline them.
+//!
+//! Only the small part of the Language Server Protocol needed for that is spoken, over any pair
+//! of streams (stdio for `poetry-udeps lsp`). Findings about declared dependencies are published
+//! on the manifest, and missing dependencies on the first file importing them. Each diagnostic on
+//! an unused dependency offers code actions to remove it from pyproject.toml, or to add it to
+//! .poetryudepsignore.
+//!
+//! The project is checked again whenever a document changes or is saved. Open pyproject.toml and
+//! Python documents are checked as the editor has them, but other manifests are read from disk,
+//! so diagnostics are not published on them while they have unsaved changes, as their positions
+//! could be wrong.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Write as _,
+    fs,
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+use serde_json::{Value, json};
+use tracing::{debug, info};
+
+use crate::{
+    Analyzer, Groups, IGNORE_FILE,
+    fix::remove_dependencies,
+    manifest::PYPROJECT,
+    name::NormalizedName,
+    report::{Finding, FindingKind, Report},
+};
+
+/// The name diagnostics are published under.
+const SOURCE: &str = env!("CARGO_PKG_NAME");
+
+/// JSON-RPC error codes.
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_REQUEST: i64 = -32600;
+
+/// Read a message, or `None` at the end of the input.
+fn read_message(input: &mut impl BufRead) -> Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("Content-Length")
+        {
+            length = Some(value.trim().parse::<usize>()?);
+        }
+    }
+    let Some(length) = length else {
+        bail!("missing Content-Length header");
+    };
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+/// Write a message, with the header the protocol frames it with.
+fn write_message(output: &mut impl Write, message: &Value) -> Result<()> {
+    let body = serde_json::to_string(message)?;
+    write!(output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    output.flush()?;
+    Ok(())
+}
+
+/// Turn an absolute path into a `file:` URI.
+///
+/// On Windows, `C:\Users` becomes `file:///C%3A/Users`, and `\\server\share` becomes
+/// `file://server/share`.
+fn path_to_uri(path: &Path) -> String {
+    let mut path = path.to_string_lossy().into_owned();
+    if cfg!(windows) {
+        // Canonical paths are verbatim, e.g. `\\?\C:\Users` or `\\?\UNC\server\share`
+        if let Some(unc) = path.strip_prefix(r"\\?\UNC\") {
+            path = format!(r"\\{unc}");
+        } else if let Some(verbatim) = path.strip_prefix(r"\\?\") {
+            path = verbatim.to_string();
+        }
+        path = path.replace('\\', "/");
+    }
+    let mut uri = String::from("file:");
+    // A UNC path starts with its server, which is the authority of the URI
+    if !path.starts_with("//") {
+        uri.push_str("//");
+    }
+    if !path.starts_with('/') {
+        uri.push('/');
+    }
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            uri.push(char::from(byte));
+        } else {
+            let _ = write!(uri, "%{byte:02X}");
+        }
+    }
+    uri
+}
+
+/// Decode the `%XX` escapes of a URI component, leaving a `%` which starts none as it is.
+fn percent_decode(component: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(component.len());
+    let mut rest = component.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        match tail {
+            [high, low, tail @ ..]
+                if byte == b'%' && high.is_ascii_hexdigit() && low.is_ascii_hexdigit() =>
+            {
+                let hex = [*high, *low];
+                bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+                rest = tail;
+            }
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Get the path a `file:` URI points to, the reverse of [`path_to_uri`].
+///
+/// On Windows, the drive letter is taken out of the path, e.g. `file:///c%3A/Users` is
+/// `c:/Users`, and a URI with a server is a UNC path.
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let rest = uri.strip_prefix("file://")?;
+    let (host, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let path = percent_decode(path)?;
+    let path = match host {
+        "" | "localhost" => path,
+        _ if cfg!(windows) => format!("//{host}{path}"),
+        _ => return None,
+    };
+    let drive = |path: &str| {
+        let bytes = path.as_bytes();
+        bytes.len() >= 3
+            && bytes[0] == b'/'
+            && bytes[1].is_ascii_alphabetic()
+            && bytes[2] == b':'
+            && bytes.get(3).is_none_or(|byte| *byte == b'/')
+    };
+    if cfg!(windows) && drive(&path) {
+        return Some(PathBuf::from(&path[1..]));
+    }
+    Some(PathBuf::from(path))
+}
+
+/// An LSP position: a line, and a column in UTF-16 code units, both counting from 0.
+fn position(line: usize, character: usize) -> Value {
+    json!({ "line": line, "character": character })
+}
+
+/// The range of the declaration starting at `line` and `column` (counting from 1, in characters)
+/// in `text`: a quoted string, or else a name. Without a column, e.g. for an import, this is the
+/// whole line but its indentation.
+fn range(text: &str, line: Option<usize>, column: Option<usize>) -> Value {
+    let line = line.map_or(0, |line| line - 1);
+    let Some(content) = text.lines().nth(line) else {
+        return json!({ "start": position(line, 0), "end": position(line, 0) });
+    };
+    let indent = content.chars().take_while(|c| c.is_whitespace()).count();
+    let start = column.map_or(indent, |column| column - 1);
+    let mut chars = content.chars().skip(start).peekable();
+    let length: usize = match chars.peek().copied() {
+        Some(_) if column.is_none() => content.trim_end().chars().count() - start,
+        Some(quote @ ('"' | '\'')) => {
+            let inner = chars.skip(1).take_while(|c| *c != quote).count();
+            inner + 2
+        }
+        Some(_) => chars
+            .take_while(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'))
+            .count(),
+        None => 0,
+    };
+    let utf16 = |chars: usize| -> usize { content.chars().take(chars).map(char::len_utf16).sum() };
+    json!({
+        "start": position(line, utf16(start)),
+        "end": position(line, utf16(start + length)),
+    })
+}
+
+/// The position just past the end of `text`.
+fn end_of(text: &str) -> Value {
+    let line = text.matches('\n').count();
+    let last = text.rsplit('\n').next().unwrap_or_default();
+    position(line, last.encode_utf16().count())
+}
+
+/// A language server for one project.
+struct Server<W> {
+    analyzer: Analyzer,
+    output: W,
+    /// URI -> the text of each open document, as last synchronized.
+    documents: HashMap<String, String>,
+    /// The URIs diagnostics were last published on.
+    published: BTreeSet<String>,
+    shutdown: bool,
+}
+
+impl<W: Write> Server<W> {
+    fn send(&mut self, message: &Value) -> Result<()> {
+        write_message(&mut self.output, message)
+    }
+
+    fn notify(&mut self, method: &str, params: &Value) -> Result<()> {
+        self.send(&json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+    }
+
+    /// Handle a message, returning whether the server should exit.
+    fn handle(&mut self, message: &Value) -> Result<bool> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        debug!(method, "Received message");
+        let Some(id) = message.get("id").cloned() else {
+            return self.handle_notification(method, params);
+        };
+        let result = match method {
+            "initialize" => Ok(self.initialize(params)),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            }
+            "textDocument/codeAction" => Ok(self.code_actions(params)),
+            _ if self.shutdown => Err((INVALID_REQUEST, "the server is shutting down".into())),
+            _ => Err((METHOD_NOT_FOUND, format!("unsupported method `{method}`"))),
+        };
+        let response = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": code, "message": message },
+            }),
+        };
+        self.send(&response)?;
+        Ok(false)
+    }
+
+    fn handle_notification(&mut self, method: &str, params: &Value) -> Result<bool> {
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .map(ToString::to_string);
+        match (method, uri) {
+            ("exit", _) => return Ok(true),
+            ("initialized" | "textDocument/didSave", _) => self.analyze()?,
+            ("textDocument/didOpen", Some(uri)) => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri, text.to_string());
+            }
+            ("textDocument/didChange", Some(uri)) => {
+                // Only full synchronization is offered, so the last change is the whole text
+                if let Some(text) = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str())
+                {
+                    self.documents.insert(uri, text.to_string());
+                    self.analyze()?;
+                }
+            }
+            // Any unsaved changes were discarded, so the file on disk holds again
+            ("textDocument/didClose", Some(uri)) if self.documents.remove(&uri).is_some() => {
+                self.analyze()?;
+            }
+            _ => (),
+        }
+        Ok(false)
+    }
+
+    fn initialize(&mut self, params: &Value) -> Value {
+        // The client knows best where the project is, unless it was given explicitly
+        let root = params["rootUri"]
+            .as_str()
+            .and_then(uri_to_path)
+            .or_else(|| params["rootPath"].as_str().map(PathBuf::from));
+        if let Some(root) = root
+            && self.analyzer.root == Path::new(".")
+        {
+            self.analyzer.root = root;
+        }
+        if let Ok(root) = self.analyzer.root.canonicalize() {
+            self.analyzer.root = root;
+        }
+        info!(root = %self.analyzer.root.display(), "Initialized");
+        json!({
+            "capabilities": {
+                "textDocumentSync": { "openClose": true, "change": 1, "save": true },
+                "codeActionProvider": { "codeActionKinds": ["quickfix"] },
+            },
+            "serverInfo": { "name": SOURCE, "version": env!("CARGO_PKG_VERSION") },
+        })
+    }
+
+    /// The text of a file, from its open document if there is one.
+    fn text(&self, path: &Path) -> Option<String> {
+        self.documents
+            .get(&path_to_uri(path))
+            .cloned()
+            .or_else(|| fs::read_to_string(path).ok())
+    }
+
+    /// Check the project as the open documents have it, and publish its findings.
+    fn analyze(&mut self) -> Result<()> {
+        self.analyzer.unsaved = self
+            .documents
+            .iter()
+            .filter_map(|(uri, text)| Some((uri_to_path(uri)?, text.clone())))
+            .filter(|(path, _)| {
+                path.extension().is_some_and(|ext| ext == "py")
+                    || path.file_name().is_some_and(|name| name == PYPROJECT)
+            })
+            .collect();
+        let report = match self.analyzer.run() {
+            Ok(report) => report,
+            Err(e) => {
+                // Most likely a half-edited manifest, so keep the last diagnostics
+                let message = format!("{e:#}");
+                return self.notify(
+                    "window/logMessage",
+                    &json!({ "type": 1, "message": message }),
+                );
+            }
+        };
+        self.publish(&report)
+    }
+
+    fn publish(&mut self, report: &Report) -> Result<()> {
+        let mut diagnostics: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+        for finding in &report.findings {
+            let Some(location) = &finding.location else {
+                continue;
+            };
+            let path = self.analyzer.root.join(&location.file);
+            let text = self.text(&path).unwrap_or_default();
+            diagnostics
+                .entry(path_to_uri(&path))
+                .or_default()
+                .push(diagnostic(
+                    finding,
+                    &range(&text, location.line, location.column),
+                ));
+        }
+        // Clear the diagnostics which no longer apply
+        for uri in &self.published {
+            diagnostics.entry(uri.clone()).or_default();
+        }
+        self.published.clear();
+        for (uri, diagnostics) in diagnostics {
+            // Positions on other manifests with unsaved changes could be wrong, as they are read
+            // from disk
+            let unsaved = self.documents.get(&uri).is_some_and(|text| {
+                uri_to_path(&uri).is_some_and(|path| {
+                    !self.analyzer.unsaved.contains_key(&path)
+                        && fs::read_to_string(path).ok().as_ref() != Some(text)
+                })
+            });
+            if unsaved {
+                self.published.insert(uri);
+                continue;
+            }
+            if !diagnostics.is_empty() {
+                self.published.insert(uri.clone());
+            }
+            self.notify(
+                "textDocument/publishDiagnostics",
+                &json!({ "uri": uri, "diagnostics": diagnostics }),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// The quick fixes for the diagnostics of a code action request.
+    fn code_actions(&self, params: &Value) -> Value {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let mut actions = Vec::new();
+        for diagnostic in params["context"]["diagnostics"]
+            .as_array()
+            .into_iter()
+            .flatten()
+        {
+            if diagnostic["source"] != SOURCE {
+                continue;
+            }
+            let Some(package) = diagnostic["data"]["package"].as_str() else {
+                continue;
+            };
+            match diagnostic["code"].as_str() {
+                Some("stale-ignore") => actions.push(unignore_action(uri, package, diagnostic)),
+                Some("unused" | "transitive") => {
+                    actions.extend(self.remove_action(uri, package, diagnostic));
+                    actions.push(self.ignore_action(package, diagnostic));
+                }
+                _ => actions.push(self.ignore_action(package, diagnostic)),
+            }
+        }
+        Value::Array(actions)
+    }
+
+    /// Remove `package` from the pyproject.toml at `uri`.
+    fn remove_action(&self, uri: &str, package: &str, diagnostic: &Value) -> Option<Value> {
+        let path = uri_to_path(uri)?;
+        if path.file_name()? != PYPROJECT {
+            return None;
+        }
+        let text = self.text(&path)?;
+        let packages = BTreeSet::from([NormalizedName::new(package)]);
+        let dev = self.analyzer.groups != Groups::Main;
+        let (fixed, removed) = remove_dependencies(&text, &packages, dev).ok()?;
+        if removed.is_empty() {
+            return None;
+        }
+        let edit = json!({
+            "range": { "start": position(0, 0), "end": end_of(&text) },
+            "newText": fixed,
+        });
+        Some(json!({
+            "title": format!("Remove {package} from {PYPROJECT}"),
+            "kind": "quickfix",
+            "diagnostics": [diagnostic],
+            "isPreferred": true,
+            "edit": { "changes": { uri: [edit] } },
+        }))
+    }
+
+    /// Add `package` to the ignore file, creating it if need be.
+    fn ignore_action(&self, package: &str, diagnostic: &Value) -> Value {
+        let path = self.analyzer.root.join(IGNORE_FILE);
+        let uri = path_to_uri(&path);
+        let text = self.text(&path).unwrap_or_default();
+        let separator = if text.is_empty() || text.ends_with('\n') {
+            ""
+        } else {
+            "\n"
+        };
+        json!({
+            "title": format!("Ignore {package} in {IGNORE_FILE}"),
+            "kind": "quickfix",
+            "diagnostics": [diagnostic],
+            "edit": {
+                "documentChanges": [
+                    {
+                        "kind": "create",
+                        "uri": uri,
+                        "options": { "overwrite": false, "ignoreIfExists": true },
+                    },
+                    {
+                        "textDocument": { "uri": uri, "version": null },
+                        "edits": [{
+                            "range": { "start": end_of(&text), "end": end_of(&text) },
+                            "newText": format!("{separator}{package}\n"),
+                        }],
+                    },
+                ],
+            },
+        })
+    }
+}
+
+/// Remove the stale entry for `package` from the ignore file at `uri`.
+fn unignore_action(uri: &str, package: &str, diagnostic: &Value) -> Value {
+    let line = diagnostic["range"]["start"]["line"]
+        .as_u64()
+        .unwrap_or_default();
+    let edit = json!({
+        "range": { "start": { "line": line, "character": 0 }, "end": { "line": line + 1, "character": 0 } },
+        "newText": "",
+    });
+    json!({
+        "title": format!("Remove {package} from {IGNORE_FILE}"),
+        "kind": "quickfix",
+        "diagnostics": [diagnostic],
+        "isPreferred": true,
+        "edit": { "changes": { uri: [edit] } },
+    })
+}
+
+/// A diagnostic describing `finding`.
+fn diagnostic(finding: &Finding, range: &Value) -> Value {
+    let severity = match finding.kind {
+        // Warning and information
+        FindingKind::Unused | FindingKind::Missing | FindingKind::Misplaced => 2,
+        FindingKind::Transitive | FindingKind::StaleIgnore => 3,
+    };
+    json!({
+        "range": range,
+        "severity": severity,
+        "source": SOURCE,
+        "code": finding.kind.rule_id(),
+        "message": finding.message(),
+        "data": { "package": finding.package },
+    })
+}
+
+/// Serve `analyzer`'s project, reading messages from `input` and writing to `output` until the
+/// client exits, returning whether it shut the server down first.
+pub fn serve(analyzer: Analyzer, mut input: impl BufRead, output: impl Write) -> Result<bool> {
+    let mut server = Server {
+        analyzer,
+        output,
+        documents: HashMap::new(),
+        published: BTreeSet::new(),
+        shutdown: false,
+    };
+    while let Some(message) = read_message(&mut input).context("failed to read a message")? {
+        if server.handle(&message)? {
+            if !server.shutdown {
+                info!("The client exited without shutting down the server");
+            }
+            return Ok(server.shutdown);
+        }
+    }
+    bail!("the client closed the connection without exiting");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::{
+        io::{BufReader, PipeReader, PipeWriter, pipe},
+        thread::{self, JoinHandle},
+    };
+
+    /// A language client, talking to a server on another thread.
+    struct Client {
+        input: PipeWriter,
+        output: BufReader<PipeReader>,
+        server: JoinHandle<Result<bool>>,
+        id: u64,
+    }
+
+    impl Client {
+        fn start(root: &Path) -> Client {
+            let (server_input, input) = pipe().unwrap();
+            let (output, server_output) = pipe().unwrap();
+            let analyzer = Analyzer::new(".");
+            let server =
+                thread::spawn(move || serve(analyzer, BufReader::new(server_input), server_output));
+            let mut client = Client {
+                input,
+                output: BufReader::new(output),
+                server,
+                id: 0,
+            };
+            let result = client.request("initialize", &json!({ "rootUri": path_to_uri(root) }));
+            assert_eq!(result["capabilities"]["textDocumentSync"]["change"], 1);
+            client.notify("initialized", &json!({}));
+            client
+        }
+
+        fn notify(&mut self, method: &str, params: &Value) {
+            let message = json!({ "jsonrpc": "2.0", "method": method, "params": params });
+            write_message(&mut self.input, &message).unwrap();
+        }
+
+        /// Send a request, returning its result.
+        fn request(&mut self, method: &str, params: &Value) -> Value {
+            self.id += 1;
+            let message =
+                json!({ "jsonrpc": "2.0", "id": self.id, "method": method, "params": params });
+            write_message(&mut self.input, &message).unwrap();
+            loop {
+                let message = read_message(&mut self.output).unwrap().unwrap();
+                if message["id"] == self.id {
+                    return message["result"].clone();
+                }
+            }
+        }
+
+        /// Wait for the next diagnostics published.
+        fn diagnostics(&mut self) -> (String, Vec<Value>) {
+            loop {
+                let message = read_message(&mut self.output).unwrap().unwrap();
+                if message["method"] == "textDocument/publishDiagnostics" {
+                    let params = &message["params"];
+                    let diagnostics = params["diagnostics"].as_array().unwrap().clone();
+                    return (params["uri"].as_str().unwrap().to_string(), diagnostics);
+                }
+            }
+        }
+
+        /// Exit, returning whether the server was shut down first.
+        fn exit(mut self, shutdown: bool) -> bool {
+            if shutdown {
+                assert_eq!(self.request("shutdown", &Value::Null), Value::Null);
+            }
+            self.notify("exit", &Value::Null);
+            self.server.join().unwrap().unwrap()
+        }
+    }
+
+    #[test]
+    fn test_uri() {
+        let path = Path::new("/home/me/my project/pyproject.toml");
+        let uri = path_to_uri(path);
+        assert_eq!(uri, "file:///home/me/my%20project/pyproject.toml");
+        assert_eq!(uri_to_path(&uri).as_deref(), Some(path));
+
+        let path = uri_to_path("file://localhost/home/me/caf%C3%A9%.py").unwrap();
+        assert_eq!(path, Path::new("/home/me/café%.py"));
+        if cfg!(windows) {
+            let path = Path::new(r"C:\Users\me\pyproject.toml");
+            let uri = path_to_uri(path);
+            assert_eq!(uri, "file:///C%3A/Users/me/pyproject.toml");
+            assert_eq!(uri_to_path(&uri).as_deref(), Some(path));
+            let path = uri_to_path("file:///c:/Users/me/app.py").unwrap();
+            assert_eq!(path, Path::new(r"c:\Users\me\app.py"));
+            let path = Path::new(r"\\server\share\pyproject.toml");
+            assert_eq!(path_to_uri(path), "file://server/share/pyproject.toml");
+            assert_eq!(
+                uri_to_path("file://server/share/pyproject.toml").as_deref(),
+                Some(path)
+            );
+        } else {
+            assert_eq!(uri_to_path("file://server/share/pyproject.toml"), None);
+        }
+    }
+
+    #[test]
+    fn test_range() {
+        let text = "[project]\ndependencies = [\"requests>=2\", \"PyYAML\"]\n";
+        assert_eq!(
+            range(text, Some(2), Some(17)),
+            json!({ "start": position(1, 16), "end": position(1, 29) })
+        );
+        let text = "[tool.poetry.dependencies]\nPyYAML = \"*\"\n";
+        assert_eq!(
+            range(text, Some(2), Some(1)),
+            json!({ "start": position(1, 0), "end": position(1, 6) })
+        );
+    }
+
+    #[test]
+    fn test_server() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path().canonicalize().unwrap();
+        let pyproject = "[project]\ndependencies = [\"requests\", \"PyYAML\"]\n";
+        fs::write(root.join(PYPROJECT), pyproject).unwrap();
+        fs::write(root.join("app.py"), "import requests\nimport numpy\n").unwrap();
+        let pyproject_uri = path_to_uri(&root.join(PYPROJECT));
+
+        let mut client = Client::start(&root);
+        let mut published = BTreeMap::from([client.diagnostics(), client.diagnostics()]);
+        let app = published
+            .remove(&path_to_uri(&root.join("app.py")))
+            .unwrap();
+        assert_eq!(app[0]["code"], "missing");
+        assert_eq!(
+            app[0]["range"],
+            json!({ "start": position(1, 0), "end": position(1, 12) })
+        );
+        let unused = published.remove(&pyproject_uri).unwrap();
+        assert_eq!(unused.len(), 1);
+        assert_eq!(unused[0]["code"], "unused");
+        assert_eq!(unused[0]["message"], "PyYAML (group: main) is unused");
+        assert_eq!(
+            unused[0]["range"],
+            json!({ "start": position(1, 28), "end": position(1, 36) })
+        );
+
+        let actions = client.request(
+            "textDocument/codeAction",
+            &json!({
+                "textDocument": { "uri": pyproject_uri },
+                "range": unused[0]["range"],
+                "context": { "diagnostics": unused },
+            }),
+        );
+        let titles: Vec<&str> = actions
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|action| action["title"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            titles,
+            [
+                "Remove PyYAML from pyproject.toml",
+                "Ignore PyYAML in .poetryudepsignore"
+            ]
+        );
+        assert_eq!(
+            actions[0]["edit"]["changes"][&pyproject_uri][0]["newText"],
+            "[project]\ndependencies = [\"requests\"]\n"
+        );
+
+        // Using the dependency clears its diagnostic once saved
+        fs::write(
+            root.join("app.py"),
+            "import requests\nimport numpy\nimport yaml\n",
+        )
+        .unwrap();
+        client.notify(
+            "textDocument/didSave",
+            &json!({ "textDocument": { "uri": path_to_uri(&root.join("app.py")) } }),
+        );
+        let published = BTreeMap::from([client.diagnostics(), client.diagnostics()]);
+        assert_eq!(published[&pyproject_uri], Vec::<Value>::new());
+
+        // Unsaved edits are checked as they are made
+        let app_uri = path_to_uri(&root.join("app.py"));
+        client.notify(
+            "textDocument/didOpen",
+            &json!({ "textDocument": { "uri": app_uri, "text": "" } }),
+        );
+        client.notify(
+            "textDocument/didChange",
+            &json!({
+                "textDocument": { "uri": app_uri },
+                "contentChanges": [{ "text": "import requests\n" }],
+            }),
+        );
+        let published = BTreeMap::from([client.diagnostics(), client.diagnostics()]);
+        assert_eq!(published[&app_uri], Vec::<Value>::new());
+        assert_eq!(published[&pyproject_uri][0]["code"], "unused");
+        client.notify(
+            "textDocument/didOpen",
+            &json!({ "textDocument": { "uri": pyproject_uri, "text": pyproject } }),
+        );
+        client.notify(
+            "textDocument/didChange",
+            &json!({
+                "textDocument": { "uri": pyproject_uri },
+                "contentChanges": [{ "text": "[project]\ndependencies = [\"requests\"]\n" }],
+            }),
+        );
+        let (uri, diagnostics) = client.diagnostics();
+        assert_eq!(
+            (uri.as_str(), diagnostics),
+            (pyproject_uri.as_str(), vec![])
+        );
+
+        client.id += 1;
+        let hover = json!({ "jsonrpc": "2.0", "id": client.id, "method": "textDocument/hover" });
+        write_message(&mut client.input, &hover).unwrap();
+        let message = read_message(&mut client.output).unwrap().unwrap();
+        assert_eq!(message["id"], client.id);
+        assert_eq!(message["error"]["code"], METHOD_NOT_FOUND);
+        assert!(client.exit(true));
+    }
+
+    #[test]
+    fn test_exit_without_shutdown() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path().canonicalize().unwrap();
+        fs::write(
+            root.join(PYPROJECT),
+            "[project]
+dependencies = []
+",
+        )
+        .unwrap();
+        let client = Client::start(&root);
+        assert!(!client.exit(false));
+    }
+}
@@ -1,28 +1,106 @@
 use clap::Parser;
-use poetry_udeps::{Cli, run};
-use std::process;
+use poetry_udeps::{Analyzer, Cli, Command, FindingKind, Format, Report};
+use std::{io, process};
 use tracing_log::AsTrace;
 
+/// Print an error and exit with the status for failed runs.
+fn fail(e: impl std::fmt::Display) -> ! {
+    eprintln!("{e}");
+    process::exit(2)
+}
+
+/// Print the findings left once the unused dependencies not among `unused` are dealt with, e.g.
+/// by fixing them, and exit with the status they give.
+fn exit_with_remaining(report: &Report, unused: &[String]) -> ! {
+    let mut fails = false;
+    for finding in report
+        .findings
+        .iter()
+        .filter(|f| f.kind != FindingKind::Unused || unused.contains(&f.package))
+    {
+        println!("{finding}");
+        fails |= finding.kind.fails();
+    }
+    process::exit(i32::from(fails))
+}
+
 fn main() {
     let cli = Cli::parse();
 
     tracing_subscriber::fmt()
         .with_max_level(cli.verbose.log_level_filter().as_trace())
+        .with_writer(io::stderr)
         .init();
 
-    match run(&cli) {
-        Ok(Some(deps)) => {
-            for dep in deps {
-                println!("{dep}");
-            }
+    let analyzer = Analyzer::from(&cli);
+    if let Some(Command::Why { package }) = &cli.command {
+        let explanation = analyzer.explain(package).unwrap_or_else(|e| fail(e));
+        print!("{explanation}");
+        process::exit(0);
+    }
+    if let Some(Command::Lsp) = &cli.command {
+        let stdin = io::stdin().lock();
+        let shutdown = analyzer
+            .lsp(stdin, io::stdout().lock())
+            .unwrap_or_else(|e| fail(e));
+        // The protocol asks for status 1 when the client exits without shutting the server down
+        process::exit(i32::from(!shutdown));
+    }
+
+    if cli.watch {
+        let Err(e) = analyzer.watch(&mut io::stdout().lock());
+        fail(e);
+    }
+
+    let mut report = analyzer.run().unwrap_or_else(|e| fail(e));
+    for warning in &report.warnings {
+        eprintln!("warning: {warning}");
+    }
+    if cli.prune_ignore {
+        let pruned = analyzer
+            .prune_ignore(&mut report)
+            .unwrap_or_else(|e| fail(e));
+        for package in &pruned {
+            eprintln!("Removed {package} from .poetryudepsignore");
+        }
+    }
+    let udeps: Vec<String> = report.unused().map(|f| f.package.clone()).collect();
+    // The machine-readable formats carry every finding, but only fail on them when asked
+    let fails = if cli.all_findings {
+        report.fails()
+    } else {
+        !udeps.is_empty()
+    };
+    if !(cli.all_findings || matches!(cli.format, Format::Json | Format::Sarif)) {
+        report.retain_unused();
+    }
+
+    // Fixing and reviewing only print text, so clap rejects them with any other format
+    if udeps.is_empty() || !(cli.fix || cli.interactive) {
+        print!("{}", report.render(cli.format).unwrap_or_else(|e| fail(e)));
+        process::exit(i32::from(fails));
+    }
+    if cli.fix {
+        let fix = analyzer
+            .fix(&udeps, cli.dry_run)
+            .unwrap_or_else(|e| fail(e));
+        if cli.dry_run {
+            print!("{}", fix.diff());
             process::exit(1);
         }
-        Ok(None) => process::exit(0),
-        Err(e) => {
-            eprintln!("{e}");
-            process::exit(2)
+        for removal in &fix.removed {
+            eprintln!("Removed {} from {}", removal.package, removal.location);
         }
+        exit_with_remaining(&report, &fix.missing);
     }
+    let review = analyzer.review(&udeps).unwrap_or_else(|e| fail(e));
+    for removal in &review.removed {
+        eprintln!("Removed {} from {}", removal.package, removal.location);
+    }
+    for dep in &review.ignored {
+        eprintln!("Ignored {dep}");
+    }
+    exit_with_remaining(&report, &review.skipped);
 }
 
 #[cfg(test)]
@@ -33,4 +111,11 @@ mod test {
         use clap::CommandFactory;
         Cli::command().debug_assert();
     }
+
+    #[test]
+    fn verify_fix_format() {
+        assert!(Cli::try_parse_from(["poetry-udeps", "--fix", "--format", "json"]).is_err());
+        assert!(Cli::try_parse_from(["poetry-udeps", "--interactive", "-f", "sarif"]).is_err());
+        assert!(Cli::try_parse_from(["poetry-udeps", "--fix", "--dry-run"]).is_ok());
+    }
 }
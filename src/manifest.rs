@@ -0,0 +1,833 @@
+//! Reading the dependencies declared by a project.
+//!
+//! Every build tool has its own opinion about where dependencies live in pyproject.toml. Each
+//! supported tool has a [`ManifestReader`] backend, and all of them produce the same
+//! [`Manifest`]: a map of dependency groups to the requirements declared in them.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt, fs,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use serde::Serialize;
+use toml::Value;
+use toml_edit::Item;
+use tracing::{debug, info};
+
+use crate::name::NormalizedName;
+
+mod hatch;
+mod pdm;
+mod pep621;
+mod pipfile;
+mod poetry;
+mod requirements;
+mod setup_cfg;
+mod setuptools;
+mod uv;
+
+pub const PYPROJECT: &str = "pyproject.toml";
+const SETUP_CFG: &str = "setup.cfg";
+pub const PIPFILE: &str = "Pipfile";
+
+/// The group holding a project's runtime dependencies.
+pub const MAIN_GROUP: &str = "main";
+
+/// The tables (keyed by distribution name) declaring main dependencies.
+pub const MAIN_TABLES: &[&str] = &["tool.poetry.dependencies"];
+/// The arrays (of PEP 508 requirements) declaring main dependencies.
+pub const MAIN_ARRAYS: &[&str] = &["project.dependencies"];
+/// The tables declaring dev dependencies. `*` matches any group or environment name.
+pub const DEV_TABLES: &[&str] = &[
+    "tool.poetry.dev-dependencies",
+    "tool.poetry.group.*.dependencies",
+];
+/// The arrays declaring dev dependencies. `*` matches any group or environment name.
+pub const DEV_ARRAYS: &[&str] = &[
+    "dependency-groups.*",
+    "tool.pdm.dev-dependencies.*",
+    "tool.uv.dev-dependencies",
+    "tool.hatch.envs.*.dependencies",
+    "tool.hatch.envs.*.extra-dependencies",
+];
+
+/// Where a requirement is declared.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct Location {
+    pub file: PathBuf,
+    /// The line of the declaration, counting from 1, if known.
+    pub line: Option<usize>,
+    /// The column of the declaration, counting from 1, if known.
+    pub column: Option<usize>,
+}
+
+impl Location {
+    #[must_use]
+    pub fn file(file: &Path) -> Self {
+        // Paths are relative to the current directory, which is better left implicit
+        let file = file.strip_prefix(".").unwrap_or(file);
+        Location {
+            file: file.to_path_buf(),
+            line: None,
+            column: None,
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file.display())?;
+        if let Some(line) = self.line {
+            write!(f, ":{line}")?;
+            if let Some(column) = self.column {
+                write!(f, ":{column}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A single declared dependency.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Requirement {
+    /// The distribution name, as spelled in the manifest.
+    pub name: String,
+    pub normalized: NormalizedName,
+    /// The source tree of a path dependency.
+    ///
+    /// Relative paths are resolved against the directory of the manifest once it is read.
+    pub path: Option<PathBuf>,
+    /// The extras requested, e.g. `socks` for `requests[socks]`.
+    pub extras: BTreeSet<NormalizedName>,
+    pub location: Option<Location>,
+    /// The comment ignoring the requirement on the line declaring it, if any.
+    pub suppression: Option<Suppression>,
+}
+
+/// A `# poetry-udeps: ignore` comment at the end of a declaration, which ignores the dependency
+/// like .poetryudepsignore does, e.g. `asyncpg = "^0.29"  # poetry-udeps: ignore (loaded by
+/// sqlalchemy)`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct Suppression {
+    /// Why the dependency is ignored, given in parentheses after `ignore`.
+    pub reason: Option<String>,
+}
+
+impl Suppression {
+    /// Parse a comment, which is a suppression if it starts with `poetry-udeps:`.
+    ///
+    /// A `poetry-udeps:` comment which is not `ignore`, optionally followed by a reason in
+    /// parentheses, is an error.
+    fn parse(comment: &str) -> Result<Option<Suppression>, String> {
+        let comment = comment.trim();
+        let Some(directive) = comment.strip_prefix("poetry-udeps:") else {
+            return Ok(None);
+        };
+        let malformed = || {
+            format!(
+                "`# {comment}` is not understood, expected `# poetry-udeps: ignore` or `# poetry-udeps: ignore (reason)`"
+            )
+        };
+        let args = directive
+            .trim_start()
+            .strip_prefix("ignore")
+            .ok_or_else(malformed)?
+            .trim();
+        let reason = if args.is_empty() {
+            None
+        } else {
+            let reason = args
+                .strip_prefix('(')
+                .and_then(|args| args.strip_suffix(')'))
+                .ok_or_else(malformed)?
+                .trim();
+            Some(reason).filter(|r| !r.is_empty()).map(String::from)
+        };
+        Ok(Some(Suppression { reason }))
+    }
+}
+
+/// Find the comment on the rest of the line after a declaration.
+///
+/// Before the comment, the line may only hold the other entries of the array the declaration is
+/// in, and the commas and brackets between and after them, so a comment after several entries
+/// applies to each of them.
+fn trailing_comment(rest: &str) -> Option<&str> {
+    let mut chars = rest.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '#' => return Some(&rest[i + 1..]),
+            '"' | '\'' => {
+                let quote = c;
+                let mut escaped = false;
+                loop {
+                    let (_, c) = chars.next()?;
+                    match c {
+                        _ if escaped => escaped = false,
+                        '\\' if quote == '"' => escaped = true,
+                        c if c == quote => break,
+                        _ => (),
+                    }
+                }
+            }
+            c if c.is_whitespace() || matches!(c, ',' | ']') => (),
+            _ => return None,
+        }
+    }
+    None
+}
+
+impl fmt::Display for Suppression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.reason {
+            Some(reason) => write!(f, "ignored: {reason}"),
+            None => write!(f, "ignored by a comment where it is declared"),
+        }
+    }
+}
+
+impl Requirement {
+    pub fn new(name: impl Into<String>) -> Self {
+        let name = name.into();
+        let normalized = NormalizedName::new(&name);
+        Requirement {
+            name,
+            normalized,
+            path: None,
+            extras: BTreeSet::new(),
+            location: None,
+            suppression: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    #[must_use]
+    pub fn with_extras<S: AsRef<str>>(mut self, extras: impl IntoIterator<Item = S>) -> Self {
+        self.extras.extend(
+            extras
+                .into_iter()
+                .map(|extra| NormalizedName::new(extra.as_ref())),
+        );
+        self
+    }
+
+    /// Parse a PEP 508 requirement string, e.g. `requests[socks] >= 2.0; python_version > "3.8"`.
+    ///
+    /// Direct references to local files, e.g. `mylib @ file:///${PROJECT_ROOT}/libs/mylib`, are
+    /// path dependencies.
+    pub fn parse(requirement: &str) -> Option<Self> {
+        // The URL parser rejects placeholders like `${PROJECT_ROOT}`, so parse those by hand
+        if let Some((name, url)) = requirement.split_once(" @ ")
+            && let Some(path) = file_url_path(url.trim())
+            && let Ok(req) = pep_508::parse(name.trim())
+        {
+            return Some(
+                Requirement::new(req.name)
+                    .with_path(path)
+                    .with_extras(req.extras),
+            );
+        }
+        if let Ok(req) = pep_508::parse(requirement.trim()) {
+            let path = match req.spec {
+                Some(pep_508::Spec::Url(url)) => file_url_path(url),
+                _ => None,
+            };
+            let requirement = Requirement::new(req.name).with_extras(req.extras);
+            Some(match path {
+                Some(path) => requirement.with_path(path),
+                None => requirement,
+            })
+        } else {
+            debug!(requirement, "Skipping unparsable requirement");
+            None
+        }
+    }
+}
+
+/// The dependencies declared by a project.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Manifest {
+    /// Group name -> the requirements declared in it.
+    ///
+    /// Runtime dependencies are in [`MAIN_GROUP`]. Every other group (dev-dependencies, Poetry
+    /// groups, PEP 735 dependency groups, Hatch environments, ...) is considered a dev group.
+    pub groups: BTreeMap<String, Vec<Requirement>>,
+    /// Problems with how dependencies are declared, which are worth reporting to the user.
+    pub warnings: Vec<String>,
+    /// The kind of file the dependencies were read from, as set by [`read`].
+    pub kind: ManifestKind,
+}
+
+impl Manifest {
+    pub fn main(&self) -> Option<&[Requirement]> {
+        self.groups.get(MAIN_GROUP).map(Vec::as_slice)
+    }
+
+    /// All requirements outside of the main group, or `None` if there are no such groups.
+    pub fn dev(&self) -> Option<Vec<&Requirement>> {
+        let mut dev = self
+            .groups
+            .iter()
+            .filter(|(group, _)| *group != MAIN_GROUP)
+            .peekable();
+        dev.peek()?;
+        Some(dev.flat_map(|(_, reqs)| reqs).collect())
+    }
+
+    /// Resolve the relative paths of path dependencies against `root`.
+    pub fn resolve_paths(&mut self, root: &Path) {
+        for req in self.groups.values_mut().flatten() {
+            if let Some(path) = &mut req.path
+                && path.is_relative()
+            {
+                *path = root.join(&*path);
+            }
+        }
+    }
+
+    /// Record that the requirements without a location are declared in `file`.
+    pub fn set_file(&mut self, file: &Path) {
+        for req in self.groups.values_mut().flatten() {
+            if req.location.is_none() {
+                req.location = Some(Location::file(file));
+            }
+        }
+    }
+
+    /// Add requirements to a group, creating the group if needed.
+    pub fn extend(&mut self, group: &str, reqs: impl IntoIterator<Item = Requirement>) {
+        self.groups
+            .entry(group.to_string())
+            .or_default()
+            .extend(reqs);
+    }
+}
+
+/// The context shared by every [`ManifestReader`].
+pub struct Pyproject {
+    /// The directory containing pyproject.toml, which relative paths are resolved against.
+    pub root: PathBuf,
+    pub value: Value,
+}
+
+impl Pyproject {
+    pub fn parse(root: PathBuf, toml: &str) -> Result<Self> {
+        let value = toml.parse::<Value>()?;
+        Ok(Pyproject { root, value })
+    }
+
+    /// Look up a dotted path of table keys, e.g. `tool.poetry.dependencies`.
+    pub fn get(&self, path: &str) -> Option<&Value> {
+        path.split('.')
+            .try_fold(&self.value, |value, key| value.get(key))
+    }
+}
+
+/// A backend reading the dependencies of projects managed by a specific build tool.
+pub trait ManifestReader {
+    fn read(&self, pyproject: &Pyproject) -> Result<Manifest>;
+}
+
+/// The build tools with a [`ManifestReader`] backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Poetry,
+    Hatch,
+    Pdm,
+    Flit,
+    Setuptools,
+    Uv,
+    /// Any other PEP 621 build backend.
+    Pep621,
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Backend::Poetry => "poetry",
+            Backend::Hatch => "hatch",
+            Backend::Pdm => "pdm",
+            Backend::Flit => "flit",
+            Backend::Setuptools => "setuptools",
+            Backend::Uv => "uv",
+            Backend::Pep621 => "pep621",
+        };
+        f.write_str(name)
+    }
+}
+
+impl Backend {
+    /// Pick the backend for a project based on its `[build-system]`.
+    ///
+    /// The build backend decides, since `[tool.*]` tables of other tools are common, e.g.
+    /// `[tool.uv]` in a project built with Hatch. Projects without a recognized build backend
+    /// fall back to whichever tool has a `[tool.*]` table.
+    pub fn detect(pyproject: &Pyproject) -> Self {
+        let build_backend = pyproject
+            .get("build-system.build-backend")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let module = build_backend.split(['.', ':']).next().unwrap_or_default();
+        let has_tool = |tool: &str| pyproject.get(&format!("tool.{tool}")).is_some();
+
+        let backend = match module {
+            "poetry" => Backend::Poetry,
+            "uv_build" => Backend::Uv,
+            "hatchling" => Backend::Hatch,
+            "pdm" => Backend::Pdm,
+            "flit_core" | "flit" => Backend::Flit,
+            "setuptools" => Backend::Setuptools,
+            _ if has_tool("poetry") => Backend::Poetry,
+            _ if has_tool("uv") => Backend::Uv,
+            _ if has_tool("pdm") => Backend::Pdm,
+            _ if has_tool("hatch") => Backend::Hatch,
+            _ if has_tool("setuptools") => Backend::Setuptools,
+            _ => Backend::Pep621,
+        };
+        info!(build_backend, %backend, "Detected build tool");
+        backend
+    }
+
+    pub fn reader(self) -> Box<dyn ManifestReader> {
+        match self {
+            Backend::Poetry => Box::new(poetry::PoetryReader),
+            Backend::Hatch => Box::new(hatch::HatchReader),
+            Backend::Pdm => Box::new(pdm::PdmReader),
+            Backend::Setuptools => Box::new(setuptools::SetuptoolsReader),
+            Backend::Uv => Box::new(uv::UvReader),
+            Backend::Flit | Backend::Pep621 => Box::new(pep621::Pep621Reader),
+        }
+    }
+}
+
+/// The kinds of files dependencies can be declared in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+#[non_exhaustive]
+pub enum ManifestKind {
+    /// The first of pyproject.toml, setup.cfg, Pipfile or requirements*.txt declaring
+    /// dependencies.
+    #[default]
+    Auto,
+    /// pyproject.toml, read according to the project's build tool.
+    Pyproject,
+    /// `install_requires` in setup.cfg.
+    SetupCfg,
+    /// Pipfile, as used by Pipenv.
+    Pipfile,
+    /// requirements.txt, and groups like requirements-dev.txt.
+    Requirements,
+}
+
+impl ManifestKind {
+    /// The kind of manifest a file is, from its name.
+    #[must_use]
+    pub fn from_path(path: &Path) -> Option<ManifestKind> {
+        let name = path.file_name()?.to_str()?;
+        match name {
+            PYPROJECT => Some(ManifestKind::Pyproject),
+            SETUP_CFG => Some(ManifestKind::SetupCfg),
+            PIPFILE => Some(ManifestKind::Pipfile),
+            _ => requirements::group(name).map(|_| ManifestKind::Requirements),
+        }
+    }
+}
+
+/// Read the dependencies declared by the project in `root`, taking pyproject.toml to hold its
+/// `unsaved` contents, if any.
+pub fn read(root: &Path, kind: ManifestKind, unsaved: Option<&str>) -> Result<Manifest> {
+    let mut manifest = match kind {
+        ManifestKind::Pyproject => read_pyproject(&root.join(PYPROJECT), unsaved)?,
+        ManifestKind::SetupCfg => setup_cfg::read(&root.join(SETUP_CFG))?,
+        ManifestKind::Pipfile => pipfile::read(&root.join(PIPFILE))?,
+        ManifestKind::Requirements => requirements::read(root)?,
+        ManifestKind::Auto => {
+            // setuptools projects often have a pyproject.toml only for `[build-system]`
+            let candidates = [
+                (
+                    ManifestKind::Pyproject,
+                    unsaved.is_some() || root.join(PYPROJECT).is_file(),
+                ),
+                (ManifestKind::SetupCfg, root.join(SETUP_CFG).is_file()),
+                (ManifestKind::Pipfile, root.join(PIPFILE).is_file()),
+                (
+                    ManifestKind::Requirements,
+                    !requirements::discover(root)?.is_empty(),
+                ),
+            ];
+            for (kind, _) in candidates.into_iter().filter(|(_, exists)| *exists) {
+                let manifest = read(root, kind, unsaved)?;
+                if manifest.main().is_some() {
+                    info!(?kind, "Reading dependencies");
+                    return Ok(manifest);
+                }
+            }
+            bail!(
+                "no dependencies found in pyproject.toml, setup.cfg, Pipfile or requirements.txt in {}. Is it the root directory of your project?",
+                root.display()
+            )
+        }
+    };
+    manifest.kind = kind;
+    Ok(manifest)
+}
+
+/// Read the dependencies declared in the requirements file at `path`, and the files it includes,
+/// rather than in every requirements*.txt file next to it.
+pub fn read_requirements(path: &Path) -> Result<Manifest> {
+    let mut manifest = requirements::read_one(path)?;
+    manifest.kind = ManifestKind::Requirements;
+    Ok(manifest)
+}
+
+/// Read the dependencies declared in a pyproject.toml file, or in its `unsaved` contents.
+pub fn read_pyproject(path: &Path, unsaved: Option<&str>) -> Result<Manifest> {
+    let toml = match unsaved {
+        Some(toml) => toml.to_string(),
+        None => fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?,
+    };
+    let root = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let pyproject = Pyproject::parse(root, &toml)
+        .with_context(|| format!("failed to parse {}", path.display()))?;
+    let mut manifest = Backend::detect(&pyproject).reader().read(&pyproject)?;
+    manifest.resolve_paths(&pyproject.root);
+    locate_declarations(
+        &mut manifest,
+        &toml,
+        path,
+        &[MAIN_ARRAYS, MAIN_TABLES].concat(),
+        &[DEV_TABLES, DEV_ARRAYS].concat(),
+    );
+    manifest.set_file(path);
+    Ok(manifest)
+}
+
+/// Record the line and column each requirement is declared at in the TOML file `file`, and the
+/// comment suppressing it, if any.
+///
+/// Malformed suppression comments are added to the manifest's warnings.
+///
+/// `toml::Value` discards spans, so the file is parsed again with `toml_edit`. Each requirement
+/// is looked up by name in the tables and arrays its group may be declared in, given as dotted
+/// paths where `*` is the group's name.
+fn locate_declarations(
+    manifest: &mut Manifest,
+    toml: &str,
+    file: &Path,
+    main: &[&str],
+    dev: &[&str],
+) {
+    let Ok(doc) = toml_edit::Document::parse(toml) else {
+        return;
+    };
+    for (group, reqs) in &mut manifest.groups {
+        let paths = if group == MAIN_GROUP { main } else { dev };
+        let items: Vec<&Item> = paths
+            .iter()
+            .filter_map(|path| {
+                path.replace('*', group)
+                    .split('.')
+                    .try_fold(doc.as_item(), |item, key| item.get(key))
+            })
+            .collect();
+        for req in reqs.iter_mut().filter(|req| req.location.is_none()) {
+            let Some(span) = items.iter().find_map(|item| declaration_span(item, req)) else {
+                continue;
+            };
+            let (line, column) = line_column(toml, span.start);
+            req.location = Some(Location {
+                line: Some(line),
+                column: Some(column),
+                ..Location::file(file)
+            });
+            let rest = toml[span.end..].lines().next().unwrap_or_default();
+            match trailing_comment(rest).map(Suppression::parse) {
+                Some(Ok(suppression)) => req.suppression = suppression,
+                Some(Err(message)) => {
+                    // Once for a line declaring several requirements
+                    let warning = format!("line {line}: {message}");
+                    if !manifest.warnings.contains(&warning) {
+                        manifest.warnings.push(warning);
+                    }
+                }
+                None => (),
+            }
+            if let Some(suppression) = &req.suppression {
+                debug!(package = req.name, %suppression, "Ignored by a comment");
+            }
+        }
+    }
+}
+
+/// Find where `req` is declared in a table keyed by distribution name, from its key to the end of
+/// its value, or in an array of PEP 508 requirements.
+fn declaration_span(item: &Item, req: &Requirement) -> Option<Range<usize>> {
+    if let Some(table) = item.as_table_like() {
+        let (name, _) = table
+            .iter()
+            .find(|(name, _)| NormalizedName::new(name) == req.normalized)?;
+        let (key, value) = table.get_key_value(name)?;
+        let key = key.span()?;
+        // The span of a table declared under its own header is the whole table
+        let end = value
+            .as_value()
+            .and_then(toml_edit::Value::span)
+            .map_or(key.end, |value| value.end);
+        return Some(key.start..end);
+    }
+    item.as_array()?
+        .iter()
+        .find(|value| {
+            value
+                .as_str()
+                .and_then(Requirement::parse)
+                .is_some_and(|r| r.normalized == req.normalized)
+        })?
+        .span()
+}
+
+/// Convert a byte offset into a line and column, both counting from 1.
+fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+/// Get the local path a `file:` URL points to.
+///
+/// The project root placeholders used by PDM (`${PROJECT_ROOT}`) and Hatch (`{root:uri}`) are
+/// kept relative, to be resolved against the manifest's directory.
+fn file_url_path(url: &str) -> Option<PathBuf> {
+    let path = url.strip_prefix("file:")?;
+    for root in ["///${PROJECT_ROOT}", "//${PROJECT_ROOT}", "{root:uri}"] {
+        if let Some(relative) = path.strip_prefix(root) {
+            return Some(PathBuf::from(format!(".{relative}")));
+        }
+    }
+    let path = path.strip_prefix("//").unwrap_or(path);
+    Some(PathBuf::from(path))
+}
+
+/// Parse an array of PEP 508 requirement strings, skipping anything else.
+fn requirement_array(value: Option<&Value>) -> Option<Vec<Requirement>> {
+    Some(
+        value?
+            .as_array()?
+            .iter()
+            .filter_map(Value::as_str)
+            .filter_map(Requirement::parse)
+            .collect(),
+    )
+}
+
+/// The names of `reqs`, for tests which do not care where they are declared.
+#[cfg(test)]
+pub(crate) fn names(reqs: &[Requirement]) -> Vec<&str> {
+    reqs.iter().map(|req| req.name.as_str()).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_kind_from_path() {
+        let kind = |path: &str| ManifestKind::from_path(Path::new(path));
+        assert_eq!(kind("app/pyproject.toml"), Some(ManifestKind::Pyproject));
+        assert_eq!(kind("setup.cfg"), Some(ManifestKind::SetupCfg));
+        assert_eq!(kind("../Pipfile"), Some(ManifestKind::Pipfile));
+        assert_eq!(
+            kind("requirements-dev.txt"),
+            Some(ManifestKind::Requirements)
+        );
+        assert_eq!(kind("app/setup.py"), None);
+    }
+
+    fn pyproject(toml: &str) -> Pyproject {
+        Pyproject::parse(PathBuf::new(), toml).unwrap()
+    }
+
+    #[test]
+    fn test_detect() {
+        let cases = [
+            ("poetry.core.masonry.api", "", Backend::Poetry),
+            ("hatchling.build", "", Backend::Hatch),
+            ("hatchling.build", "[tool.uv]\n", Backend::Hatch),
+            ("pdm.backend", "[tool.poetry]\n", Backend::Pdm),
+            ("pdm.backend", "", Backend::Pdm),
+            ("flit_core.buildapi", "", Backend::Flit),
+            ("setuptools.build_meta:__legacy__", "", Backend::Setuptools),
+            ("uv_build", "", Backend::Uv),
+            ("maturin", "", Backend::Pep621),
+            ("maturin", "[tool.uv]\n", Backend::Uv),
+        ];
+        for (build_backend, tools, expected) in cases {
+            let toml = format!("{tools}[build-system]\nbuild-backend = \"{build_backend}\"\n");
+            assert_eq!(
+                Backend::detect(&pyproject(&toml)),
+                expected,
+                "{build_backend}"
+            );
+        }
+        assert_eq!(
+            Backend::detect(&pyproject("[tool.poetry]\nname = \"x\"\n")),
+            Backend::Poetry
+        );
+    }
+
+    #[test]
+    fn test_file_url_requirement() {
+        let req = Requirement::parse("mylib @ file:///${PROJECT_ROOT}/libs/mylib").unwrap();
+        assert_eq!(req.path, Some(PathBuf::from("./libs/mylib")));
+        let req = Requirement::parse("other @ file:///opt/src/other").unwrap();
+        assert_eq!(req.path, Some(PathBuf::from("/opt/src/other")));
+        let req = Requirement::parse("requests @ https://example.com/requests.whl").unwrap();
+        assert_eq!(req.path, None);
+    }
+
+    #[test]
+    fn test_locate_declarations() {
+        let toml = r#"[project]
+dependencies = [
+    "requests>=2",
+    "PyYAML",
+]
+
+[tool.poetry.group.test.dependencies]
+"pytest-mock" = "*"
+
+[dependency-groups]
+lint = ["ruff"]
+"#;
+        let mut manifest = Manifest::default();
+        manifest.extend(MAIN_GROUP, ["requests", "pyyaml"].map(Requirement::new));
+        manifest.extend("test", [Requirement::new("pytest_mock")]);
+        manifest.extend("lint", ["ruff", "mypy"].map(Requirement::new));
+        locate_declarations(
+            &mut manifest,
+            toml,
+            Path::new("./pyproject.toml"),
+            &[MAIN_ARRAYS, MAIN_TABLES].concat(),
+            &[DEV_TABLES, DEV_ARRAYS].concat(),
+        );
+        manifest.set_file(Path::new("./pyproject.toml"));
+        let locations: Vec<String> = manifest
+            .groups
+            .values()
+            .flatten()
+            .map(|req| req.location.as_ref().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            locations,
+            [
+                "pyproject.toml:11:9",
+                "pyproject.toml",
+                "pyproject.toml:3:5",
+                "pyproject.toml:4:5",
+                "pyproject.toml:8:1"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_suppression() {
+        let toml = r#"[project]
+dependencies = [
+    "requests>=2",  # poetry-udeps: ignore
+    "PyYAML",  # needed by the plugins
+    "click", "rich",  # poetry-udeps: ignore (one line, both)
+]
+
+[tool.poetry.dependencies]
+asyncpg = "^0.29"  # poetry-udeps: ignore (loaded by sqlalchemy)
+boto3 = { version = "*", extras = ["crt"] }  # poetry-udeps:ignore()
+numpy = "*"  # poetry-udeps: ignore because
+
+[tool.poetry.dependencies.psycopg]  # poetry-udeps: ignore (the driver)
+version = "*"
+"#;
+        let mut manifest = Manifest::default();
+        manifest.extend(
+            MAIN_GROUP,
+            ["requests", "pyyaml", "click", "rich"].map(Requirement::new),
+        );
+        manifest.extend(
+            "poetry",
+            ["asyncpg", "boto3", "numpy", "psycopg"].map(Requirement::new),
+        );
+        locate_declarations(
+            &mut manifest,
+            toml,
+            Path::new("pyproject.toml"),
+            MAIN_ARRAYS,
+            &["tool.poetry.dependencies"],
+        );
+        let suppressions: Vec<(&str, Option<&str>)> = manifest
+            .groups
+            .values()
+            .flatten()
+            .filter_map(|req| {
+                let suppression = req.suppression.as_ref()?;
+                Some((req.name.as_str(), suppression.reason.as_deref()))
+            })
+            .collect();
+        assert_eq!(
+            suppressions,
+            [
+                ("requests", None),
+                ("click", Some("one line, both")),
+                ("rich", Some("one line, both")),
+                ("asyncpg", Some("loaded by sqlalchemy")),
+                ("boto3", None),
+                ("psycopg", Some("the driver")),
+            ]
+        );
+        assert_eq!(
+            manifest.warnings,
+            [
+                "line 11: `# poetry-udeps: ignore because` is not understood, expected `# poetry-udeps: ignore` or `# poetry-udeps: ignore (reason)`"
+            ]
+        );
+        // A `#` in another entry does not start the comment
+        assert_eq!(
+            trailing_comment(r#", "a @ https://x.org/a.zip#egg=a"  # c"#),
+            Some(" c")
+        );
+        assert_eq!(trailing_comment(r##", "a\"#"  # c"##), Some(" c"));
+        assert_eq!(trailing_comment(" = { version = \"*\" }  # c"), None);
+    }
+
+    #[test]
+    fn test_line_column() {
+        assert_eq!(line_column("a = 1\nbé = 2\n", 10), (2, 4));
+        assert_eq!(line_column("a = 1", 0), (1, 1));
+    }
+
+    #[test]
+    fn test_dev() {
+        let mut manifest = Manifest::default();
+        manifest.extend(MAIN_GROUP, [Requirement::new("requests")]);
+        assert_eq!(manifest.dev(), None);
+        manifest.extend("test", [Requirement::new("pytest")]);
+        manifest.extend("lint", [Requirement::new("ruff")]);
+        assert_eq!(
+            manifest.dev().unwrap(),
+            [&Requirement::new("ruff"), &Requirement::new("pytest")]
+        );
+    }
+}
@@ -0,0 +1,65 @@
+use anyhow::Result;
+use toml::Value;
+
+use super::{Manifest, ManifestReader, Pyproject, pep621::read_project, requirement_array};
+
+/// Reads projects built with Hatch.
+///
+/// Each Hatch environment is a dependency group named after the environment.
+pub struct HatchReader;
+
+impl ManifestReader for HatchReader {
+    fn read(&self, pyproject: &Pyproject) -> Result<Manifest> {
+        let mut manifest = read_project(pyproject);
+        for (env, table) in pyproject
+            .get("tool.hatch.envs")
+            .and_then(Value::as_table)
+            .into_iter()
+            .flatten()
+        {
+            for key in ["dependencies", "extra-dependencies"] {
+                if let Some(reqs) = requirement_array(table.get(key)) {
+                    manifest.extend(env, reqs);
+                }
+            }
+        }
+        Ok(manifest)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::manifest::Requirement;
+    use pretty_assertions::assert_eq;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_read() {
+        let pyproject = Pyproject::parse(
+            PathBuf::new(),
+            r#"
+[project]
+dependencies = ["httpx"]
+
+[tool.hatch.envs.default]
+dependencies = ["pytest", "coverage[toml]>=6.5"]
+
+[tool.hatch.envs.lint]
+detached = true
+extra-dependencies = ["ruff"]
+"#,
+        )
+        .unwrap();
+        let manifest = HatchReader.read(&pyproject).unwrap();
+        assert_eq!(manifest.main().unwrap(), [Requirement::new("httpx")]);
+        assert_eq!(
+            manifest.groups["default"],
+            [
+                Requirement::new("pytest"),
+                Requirement::new("coverage").with_extras(["toml"])
+            ]
+        );
+        assert_eq!(manifest.groups["lint"], [Requirement::new("ruff")]);
+    }
+}
@@ -0,0 +1,78 @@
+use std::path::Path;
+
+use anyhow::Result;
+use toml::Value;
+
+use super::{
+    Manifest, ManifestReader, Pyproject, Requirement, pep621::read_project,
+    requirements::url_requirement,
+};
+
+/// Reads projects managed by PDM.
+pub struct PdmReader;
+
+impl ManifestReader for PdmReader {
+    fn read(&self, pyproject: &Pyproject) -> Result<Manifest> {
+        let mut manifest = read_project(pyproject);
+        // PDM's own dev-dependency groups predate PEP 735
+        for (group, reqs) in pyproject
+            .get("tool.pdm.dev-dependencies")
+            .and_then(Value::as_table)
+            .into_iter()
+            .flatten()
+        {
+            if let Some(reqs) = reqs.as_array() {
+                let reqs = reqs
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .filter_map(dev_requirement);
+                manifest.extend(group, reqs);
+            }
+        }
+        Ok(manifest)
+    }
+}
+
+/// Parse a dev requirement, which may also be an editable install, e.g.
+/// `-e file:///${PROJECT_ROOT}/libs/mylib#egg=mylib`.
+///
+/// Paths are left relative to the project root, where the manifest resolves them.
+fn dev_requirement(requirement: &str) -> Option<Requirement> {
+    match requirement.strip_prefix("-e") {
+        Some(url) => url_requirement(url.trim(), Path::new("")),
+        None => Requirement::parse(requirement),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_read() {
+        let pyproject = Pyproject::parse(
+            PathBuf::new(),
+            r#"
+[project]
+dependencies = ["flask"]
+
+[tool.pdm.dev-dependencies]
+test = ["pytest", "-e file:///${PROJECT_ROOT}/libs/mylib#egg=mylib"]
+lint = ["ruff"]
+"#,
+        )
+        .unwrap();
+        let manifest = PdmReader.read(&pyproject).unwrap();
+        assert_eq!(manifest.main().unwrap(), [Requirement::new("flask")]);
+        assert_eq!(
+            manifest.groups["test"],
+            [
+                Requirement::new("pytest"),
+                Requirement::new("mylib").with_path("./libs/mylib")
+            ]
+        );
+        assert_eq!(manifest.groups["lint"], [Requirement::new("ruff")]);
+    }
+}
@@ -0,0 +1,72 @@
+use anyhow::Result;
+use toml::Value;
+
+use super::{MAIN_GROUP, Manifest, ManifestReader, Pyproject, Requirement, requirement_array};
+
+/// Reads projects which only use the standard `[project]` table, e.g. those built with Flit.
+pub struct Pep621Reader;
+
+impl ManifestReader for Pep621Reader {
+    fn read(&self, pyproject: &Pyproject) -> Result<Manifest> {
+        Ok(read_project(pyproject))
+    }
+}
+
+/// Read `project.dependencies` and the PEP 735 `[dependency-groups]` table.
+///
+/// This is the base every other PEP 621 backend builds on.
+pub fn read_project(pyproject: &Pyproject) -> Manifest {
+    let mut manifest = Manifest::default();
+    if pyproject.get("project").is_some() {
+        let reqs = requirement_array(pyproject.get("project.dependencies")).unwrap_or_default();
+        manifest.extend(MAIN_GROUP, reqs);
+    }
+    if let Some(groups) = pyproject.get("dependency-groups").and_then(Value::as_table) {
+        for (group, entries) in groups {
+            // Entries are either requirement strings or `{ include-group = "..." }` tables.
+            // Included groups are read on their own, so they are not repeated here.
+            let reqs = entries
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .filter_map(Requirement::parse);
+            manifest.extend(group, reqs);
+        }
+    }
+    manifest
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_read_project() {
+        let pyproject = Pyproject::parse(
+            PathBuf::new(),
+            r#"
+[project]
+name = "example"
+dependencies = ["requests[socks] >= 2.0", "PyYAML; python_version > '3.8'"]
+
+[dependency-groups]
+test = ["pytest"]
+dev = [{ include-group = "test" }, "ruff"]
+"#,
+        )
+        .unwrap();
+        let manifest = Pep621Reader.read(&pyproject).unwrap();
+        assert_eq!(
+            manifest.main().unwrap(),
+            [
+                Requirement::new("requests").with_extras(["socks"]),
+                Requirement::new("PyYAML")
+            ]
+        );
+        assert_eq!(manifest.groups["dev"], [Requirement::new("ruff")]);
+        assert_eq!(manifest.groups["test"], [Requirement::new("pytest")]);
+    }
+}
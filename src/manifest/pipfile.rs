@@ -0,0 +1,93 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+use toml::Value;
+
+use super::{MAIN_GROUP, Manifest, Requirement, locate_declarations};
+
+/// Read the `[packages]` and `[dev-packages]` of a Pipfile.
+pub fn read(path: &Path) -> Result<Manifest> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let value = contents
+        .parse::<Value>()
+        .with_context(|| format!("failed to parse {}", path.display()))?;
+
+    let mut manifest = Manifest::default();
+    for (table, group) in [("packages", MAIN_GROUP), ("dev-packages", "dev")] {
+        if let Some(packages) = value.get(table).and_then(Value::as_table) {
+            let reqs = packages.iter().map(|(name, spec)| {
+                let extras = spec
+                    .get("extras")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Value::as_str);
+                let requirement = Requirement::new(name).with_extras(extras);
+                match spec.get("path").and_then(Value::as_str) {
+                    Some(path) => requirement.with_path(path),
+                    None => requirement,
+                }
+            });
+            manifest.extend(group, reqs);
+        }
+    }
+    if let Some(root) = path.parent() {
+        manifest.resolve_paths(root);
+    }
+    locate_declarations(
+        &mut manifest,
+        &contents,
+        path,
+        &["packages"],
+        &["dev-packages"],
+    );
+    manifest.set_file(path);
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{manifest::names, name::NormalizedName};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_read() {
+        let root = tempfile::tempdir().unwrap();
+        let path = root.path().join("Pipfile");
+        fs::write(
+            &path,
+            r#"[[source]]
+url = "https://pypi.org/simple"
+
+[packages]
+requests = "*"
+django = {version = ">=4.2", extras = ["argon2"]}
+
+[dev-packages]
+pytest = "*"
+mylib = {path = "./libs/mylib", editable = true}
+
+[requires]
+python_version = "3.12"
+"#,
+        )
+        .unwrap();
+        let manifest = read(&path).unwrap();
+        assert_eq!(names(manifest.main().unwrap()), ["django", "requests"]);
+        let extras: Vec<&str> = manifest.main().unwrap()[0]
+            .extras
+            .iter()
+            .map(NormalizedName::as_str)
+            .collect();
+        assert_eq!(extras, ["argon2"]);
+        assert_eq!(names(&manifest.groups["dev"]), ["mylib", "pytest"]);
+        assert_eq!(
+            manifest.groups["dev"][0].path,
+            Some(root.path().join("./libs/mylib"))
+        );
+        let location = manifest.groups["dev"][1].location.as_ref().unwrap();
+        assert_eq!((location.line, location.column), (Some(9), Some(1)));
+    }
+}
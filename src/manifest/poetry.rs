@@ -0,0 +1,228 @@
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+use toml::{Table, Value};
+
+use crate::name::NormalizedName;
+
+use super::{MAIN_GROUP, Manifest, ManifestReader, Pyproject, Requirement, requirement_array};
+
+/// Reads projects managed by Poetry.
+pub struct PoetryReader;
+
+impl ManifestReader for PoetryReader {
+    fn read(&self, pyproject: &Pyproject) -> Result<Manifest> {
+        let mut manifest = Manifest::default();
+        let poetry = pyproject
+            .get("tool.poetry.dependencies")
+            .and_then(Value::as_table)
+            .map(|deps| table_requirements(deps).collect::<Vec<_>>());
+        // Check poetry >=2.0
+        let project = requirement_array(pyproject.get("project.dependencies"));
+        match (project, poetry) {
+            (Some(project), Some(poetry)) => {
+                let main = merge(project, poetry, &mut manifest.warnings);
+                manifest.extend(MAIN_GROUP, main);
+            }
+            (Some(reqs), None) | (None, Some(reqs)) => manifest.extend(MAIN_GROUP, reqs),
+            (None, None) => (),
+        }
+
+        // Check poetry >=1.0,<1.2's dev-dependencies
+        if let Some(dev) = pyproject
+            .get("tool.poetry.dev-dependencies")
+            .and_then(Value::as_table)
+        {
+            manifest.extend("dev", table_requirements(dev));
+        }
+        // Check poetry >=1.2.0's dependency groups
+        for (group, table) in pyproject
+            .get("tool.poetry.group")
+            .and_then(Value::as_table)
+            .into_iter()
+            .flatten()
+        {
+            if let Some(deps) = table.get("dependencies").and_then(Value::as_table) {
+                manifest.extend(group, table_requirements(deps));
+            }
+        }
+        Ok(manifest)
+    }
+}
+
+/// Merge the dependencies declared in `[project]` with those in `[tool.poetry]`.
+///
+/// Since Poetry 2, `tool.poetry.dependencies` may still be used alongside `project.dependencies`
+/// to add source or extra metadata, so every dependency is expected in both. Duplicates are
+/// removed by normalized name, keeping the `[project]` spelling, and dependencies declared in only
+/// one place are reported.
+fn merge(
+    project: Vec<Requirement>,
+    poetry: Vec<Requirement>,
+    warnings: &mut Vec<String>,
+) -> Vec<Requirement> {
+    let in_project: BTreeSet<NormalizedName> =
+        project.iter().map(|req| req.normalized.clone()).collect();
+    let in_poetry: BTreeSet<NormalizedName> =
+        poetry.iter().map(|req| req.normalized.clone()).collect();
+    // With only `python` in tool.poetry.dependencies, nothing is being enriched
+    if !poetry.is_empty() {
+        for req in &project {
+            if !in_poetry.contains(&req.normalized) {
+                warnings.push(format!(
+                    "{} is declared in project.dependencies but not in tool.poetry.dependencies",
+                    req.name
+                ));
+            }
+        }
+    }
+    // Path dependencies are only declared as such in tool.poetry.dependencies
+    let mut merged = project;
+    for req in &mut merged {
+        if let Some(path) = poetry
+            .iter()
+            .find(|p| p.normalized == req.normalized)
+            .and_then(|p| p.path.clone())
+        {
+            req.path = Some(path);
+        }
+    }
+    for req in poetry {
+        if !in_project.contains(&req.normalized) {
+            warnings.push(format!(
+                "{} is declared in tool.poetry.dependencies but not in project.dependencies",
+                req.name
+            ));
+            merged.push(req);
+        }
+    }
+    merged
+}
+
+/// Poetry declares dependencies as `name = <constraint>`, including the Python version.
+///
+/// Path dependencies are declared as `name = { path = "../mylib" }`, and extras as
+/// `name = { version = "*", extras = ["socks"] }`.
+fn table_requirements(deps: &Table) -> impl Iterator<Item = Requirement> + '_ {
+    deps.iter()
+        .filter(|(name, _)| *name != "python")
+        .map(|(name, constraint)| {
+            let extras = constraint
+                .get("extras")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str);
+            let requirement = Requirement::new(name).with_extras(extras);
+            match constraint.get("path").and_then(Value::as_str) {
+                Some(path) => requirement.with_path(path),
+                None => requirement,
+            }
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_read() {
+        let pyproject = Pyproject::parse(
+            PathBuf::new(),
+            r#"
+[tool.poetry.dependencies]
+python = "^3.10"
+requests = "^2.31"
+
+[tool.poetry.group.dev.dependencies]
+pytest = "^8"
+
+[tool.poetry.group.docs.dependencies]
+mkdocs = "*"
+"#,
+        )
+        .unwrap();
+        let manifest = PoetryReader.read(&pyproject).unwrap();
+        assert_eq!(manifest.main().unwrap(), [Requirement::new("requests")]);
+        assert_eq!(manifest.groups["dev"], [Requirement::new("pytest")]);
+        assert_eq!(manifest.groups["docs"], [Requirement::new("mkdocs")]);
+    }
+
+    #[test]
+    fn test_path_dependency() {
+        let pyproject = Pyproject::parse(
+            PathBuf::new(),
+            r#"
+[tool.poetry.dependencies]
+mylib = { path = "../mylib", develop = true }
+other = { git = "https://example.com/other.git" }
+"#,
+        )
+        .unwrap();
+        let manifest = PoetryReader.read(&pyproject).unwrap();
+        assert_eq!(
+            manifest.main().unwrap(),
+            [
+                Requirement::new("mylib").with_path("../mylib"),
+                Requirement::new("other")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_poetry_2() {
+        let pyproject = Pyproject::parse(
+            PathBuf::new(),
+            r#"
+[project]
+dependencies = ["requests (>=2.31,<3.0)"]
+
+[tool.poetry.dev-dependencies]
+pytest = "^8"
+"#,
+        )
+        .unwrap();
+        let manifest = PoetryReader.read(&pyproject).unwrap();
+        assert_eq!(manifest.main().unwrap(), [Requirement::new("requests")]);
+        assert_eq!(manifest.groups["dev"], [Requirement::new("pytest")]);
+    }
+
+    #[test]
+    fn test_merge_poetry_2() {
+        let pyproject = Pyproject::parse(
+            PathBuf::new(),
+            r#"
+[project]
+dependencies = ["requests (>=2.31,<3.0)", "PyYAML", "click", "corp-utils"]
+
+[tool.poetry.dependencies]
+python = "^3.10"
+requests = { source = "internal" }
+pyyaml = { extras = ["libyaml"] }
+corp_utils = { path = "../corp-utils" }
+mylib = { path = "../mylib" }
+"#,
+        )
+        .unwrap();
+        let manifest = PoetryReader.read(&pyproject).unwrap();
+        assert_eq!(
+            manifest.main().unwrap(),
+            [
+                Requirement::new("requests"),
+                Requirement::new("PyYAML"),
+                Requirement::new("click"),
+                Requirement::new("corp-utils").with_path("../corp-utils"),
+                Requirement::new("mylib").with_path("../mylib")
+            ]
+        );
+        assert_eq!(
+            manifest.warnings,
+            [
+                "click is declared in project.dependencies but not in tool.poetry.dependencies",
+                "mylib is declared in tool.poetry.dependencies but not in project.dependencies"
+            ]
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+use tracing::debug;
+
+use super::{Location, MAIN_GROUP, Manifest, Requirement, file_url_path};
+use crate::source_tree;
+
+/// Read every `requirements*.txt` file in `root`.
+///
+/// `requirements.txt` holds the main dependencies. The group of any other file is taken from its
+/// name, e.g. `requirements-dev.txt` and `dev-requirements.txt` are both the `dev` group.
+pub fn read(root: &Path) -> Result<Manifest> {
+    let files = discover(root)?;
+    if files.is_empty() {
+        bail!("no requirements files found in {}", root.display());
+    }
+
+    // A file commonly includes another with `-r requirements.txt`. Those requirements belong to
+    // the other file's group, so they are not read twice.
+    let top_level: BTreeSet<PathBuf> = files
+        .iter()
+        .filter_map(|(_, path)| fs::canonicalize(path).ok())
+        .collect();
+
+    let mut manifest = Manifest::default();
+    for (group, path) in &files {
+        let mut skip = top_level.clone();
+        if let Ok(path) = fs::canonicalize(path) {
+            skip.remove(&path);
+        }
+        manifest.extend(group, read_file(path, &mut skip)?);
+    }
+    Ok(manifest)
+}
+
+/// Read the requirements file at `path` alone, e.g. `requirements-dev.txt`.
+///
+/// Its group is taken from its name, and the files it includes belong to that group too.
+pub fn read_one(path: &Path) -> Result<Manifest> {
+    let Some(group) = path.file_name().and_then(|n| n.to_str()).and_then(group) else {
+        bail!("{} is not named like a requirements file", path.display());
+    };
+    let mut manifest = Manifest::default();
+    manifest.extend(group, read_file(path, &mut BTreeSet::new())?);
+    Ok(manifest)
+}
+
+/// Find the requirements files in `root`, along with their group.
+pub fn discover(root: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(root)? {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let Some(group) = group(name) else {
+            continue;
+        };
+        files.push((group.to_string(), path.clone()));
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// The group of a requirements file named like `requirements.txt` or `requirements-dev.txt`.
+pub fn group(file_name: &str) -> Option<&str> {
+    let stem = file_name.strip_suffix(".txt")?;
+    if stem == "requirements" {
+        return Some(MAIN_GROUP);
+    }
+    stem.strip_prefix("requirements-")
+        .or_else(|| stem.strip_prefix("requirements_"))
+        .or_else(|| stem.strip_suffix("-requirements"))
+        .or_else(|| stem.strip_suffix("_requirements"))
+}
+
+/// Read the requirements listed in a pip requirements file.
+///
+/// Files included with `-r` are read recursively, unless they are in `skip`. Every file read is
+/// added to `skip`, which guards against include cycles. Constraints files (`-c`) only restrict
+/// versions, so they do not declare any dependencies.
+pub fn read_file(path: &Path, skip: &mut BTreeSet<PathBuf>) -> Result<Vec<Requirement>> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    if let Ok(canonical) = fs::canonicalize(path) {
+        skip.insert(canonical);
+    }
+    let dir = path.parent().unwrap_or(Path::new(""));
+
+    let mut reqs = Vec::new();
+    for (number, column, line) in logical_lines(&contents) {
+        let start = reqs.len();
+        if let Some(include) = option_value(&line, &["-r", "--requirement"]) {
+            let include = dir.join(include);
+            match fs::canonicalize(&include) {
+                Ok(canonical) if skip.contains(&canonical) => {
+                    debug!(path = %include.display(), "Skipping included requirements file");
+                }
+                _ => reqs.extend(read_file(&include, skip)?),
+            }
+        } else if let Some(editable) = option_value(&line, &["-e", "--editable"]) {
+            reqs.extend(url_requirement(editable, dir));
+        } else if line.starts_with('-') {
+            // Any other pip option, e.g. `-c`, `--index-url` or `--hash`
+        } else {
+            // Per-requirement options, e.g. `--hash=sha256:...`, follow the requirement
+            let req = line.split_once(" --").map_or(line.as_str(), |(req, _)| req);
+            match Requirement::parse(req) {
+                Some(req) => reqs.push(req),
+                None => reqs.extend(url_requirement(req, dir)),
+            }
+        }
+        // Requirements from included files are already located there
+        for req in &mut reqs[start..] {
+            req.location.get_or_insert_with(|| Location {
+                line: Some(number),
+                column: Some(column),
+                ..Location::file(path)
+            });
+        }
+    }
+    Ok(reqs)
+}
+
+/// Join continued lines, and strip comments and whitespace, skipping empty lines.
+///
+/// Each line is returned with the line and column it starts at, counting from 1.
+fn logical_lines(contents: &str) -> Vec<(usize, usize, String)> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    let mut start = (1, 1);
+    for (i, line) in contents.lines().enumerate() {
+        if current.is_empty() {
+            let indent = line.len() - line.trim_start().len();
+            start = (i + 1, line[..indent].chars().count() + 1);
+        }
+        if let Some(continued) = line.strip_suffix('\\') {
+            current.push_str(continued);
+            continue;
+        }
+        current.push_str(line);
+        // Comments start with `#` at the start of a line, or after whitespace
+        let line = match current.find(" #").or_else(|| current.find("\t#")) {
+            Some(i) => &current[..i],
+            None if current.trim_start().starts_with('#') => "",
+            None => &current,
+        };
+        let line = line.trim();
+        if !line.is_empty() {
+            lines.push((start.0, start.1, line.to_string()));
+        }
+        current.clear();
+    }
+    lines
+}
+
+/// Get the value of an option in either its `-o value`, `--option value` or `--option=value` form.
+fn option_value<'a>(line: &'a str, names: &[&str]) -> Option<&'a str> {
+    names.iter().find_map(|name| {
+        let rest = line.strip_prefix(name)?;
+        let value = rest
+            .strip_prefix('=')
+            .or_else(|| rest.starts_with(char::is_whitespace).then_some(rest))?;
+        Some(value.trim())
+    })
+}
+
+/// Read a requirement given as a bare URL or local path, e.g. `-e ../mylib`, relative to `dir`.
+///
+/// The project may be named in the `#egg=name` fragment. Otherwise, a local project is read to
+/// find its name.
+pub(super) fn url_requirement(url: &str, dir: &Path) -> Option<Requirement> {
+    let path = url.split('#').next().unwrap_or(url);
+    let local = if path.contains("://") {
+        file_url_path(path)
+    } else {
+        Some(PathBuf::from(path.strip_prefix("file:").unwrap_or(path)))
+    }
+    .map(|path| dir.join(path));
+    let name = match egg_name(url) {
+        Some(name) => name.to_string(),
+        None => source_tree::project_name(local.as_deref()?)?,
+    };
+    let req = Requirement::new(name);
+    Some(match local {
+        Some(path) => req.with_path(path),
+        None => req,
+    })
+}
+
+/// Get the project name from the `#egg=name` fragment of a URL.
+fn egg_name(url: &str) -> Option<&str> {
+    let (_, fragment) = url.split_once('#')?;
+    fragment
+        .split('&')
+        .find_map(|part| part.strip_prefix("egg="))
+        .filter(|name| !name.is_empty())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::manifest::names;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_read() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(
+            root.path().join("requirements.txt"),
+            "# Runtime\nrequests>=2.31 \\\n    --hash=sha256:abc  # http\n-c constraints.txt\nPyYAML\n",
+        )
+        .unwrap();
+        fs::write(root.path().join("constraints.txt"), "urllib3<2\n").unwrap();
+        fs::write(
+            root.path().join("requirements-dev.txt"),
+            "-r requirements.txt\n--requirement=requirements-lint.in\n-e git+https://example.com/mylib.git#egg=mylib\npytest\n",
+        )
+        .unwrap();
+        fs::write(root.path().join("requirements-lint.in"), "ruff\n").unwrap();
+        fs::write(root.path().join("notes.txt"), "not-a-requirement\n").unwrap();
+
+        let manifest = read(root.path()).unwrap();
+        assert_eq!(names(manifest.main().unwrap()), ["requests", "PyYAML"]);
+        assert_eq!(names(&manifest.groups["dev"]), ["ruff", "mylib", "pytest"]);
+        assert_eq!(manifest.groups.len(), 2);
+
+        let locations: Vec<String> = manifest.groups["dev"]
+            .iter()
+            .map(|req| {
+                let location = req.location.as_ref().unwrap();
+                format!(
+                    "{}:{}",
+                    location.file.file_name().unwrap().to_string_lossy(),
+                    location.line.unwrap()
+                )
+            })
+            .collect();
+        assert_eq!(
+            locations,
+            [
+                "requirements-lint.in:1",
+                "requirements-dev.txt:3",
+                "requirements-dev.txt:4"
+            ]
+        );
+        let requests = &manifest.main().unwrap()[0];
+        assert_eq!(requests.location.as_ref().unwrap().line, Some(2));
+    }
+
+    #[test]
+    fn test_read_one() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(root.path().join("requirements.txt"), "requests\n").unwrap();
+        fs::write(root.path().join("requirements-lint.txt"), "ruff\n").unwrap();
+        fs::write(
+            root.path().join("requirements-dev.txt"),
+            "-r requirements.txt\npytest\n",
+        )
+        .unwrap();
+
+        let manifest = read_one(&root.path().join("requirements-dev.txt")).unwrap();
+        assert_eq!(names(&manifest.groups["dev"]), ["requests", "pytest"]);
+        assert_eq!(manifest.groups.len(), 1);
+        assert!(read_one(&root.path().join("notes.txt")).is_err());
+    }
+
+    #[test]
+    fn test_local_paths() {
+        let root = tempfile::tempdir().unwrap();
+        let lib = root.path().join("libs/corp-utils");
+        fs::create_dir_all(&lib).unwrap();
+        fs::write(
+            lib.join("pyproject.toml"),
+            "[project]\nname = \"corp-utils\"\n",
+        )
+        .unwrap();
+        let other = root.path().join("libs/other");
+        fs::write(
+            root.path().join("requirements.txt"),
+            format!(
+                "-e ./libs/corp-utils\n./libs/missing\n-e file://{}#egg=other\n",
+                other.display()
+            ),
+        )
+        .unwrap();
+        let manifest = read(root.path()).unwrap();
+        assert_eq!(names(manifest.main().unwrap()), ["corp-utils", "other"]);
+        assert_eq!(
+            manifest.main().unwrap()[0].path,
+            Some(root.path().join("./libs/corp-utils"))
+        );
+        assert_eq!(manifest.main().unwrap()[1].path, Some(other));
+    }
+
+    #[test]
+    fn test_include_cycle() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(root.path().join("a.txt"), "-r b.txt\nflask\n").unwrap();
+        fs::write(root.path().join("b.txt"), "-r a.txt\nclick\n").unwrap();
+        let reqs = read_file(&root.path().join("a.txt"), &mut BTreeSet::new()).unwrap();
+        assert_eq!(names(&reqs), ["click", "flask"]);
+    }
+}
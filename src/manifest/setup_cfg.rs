@@ -0,0 +1,106 @@
+use std::{collections::BTreeSet, fs, path::Path};
+
+use anyhow::{Context, Result};
+
+use super::{MAIN_GROUP, Manifest, Requirement, requirements};
+
+/// Read the `install_requires` and `tests_require` options of a setup.cfg file.
+///
+/// Values are either a dangling list with one requirement per line, or a `file:` directive
+/// pointing at requirements files.
+pub fn read(path: &Path) -> Result<Manifest> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+
+    let mut manifest = Manifest::default();
+    for (key, group) in [("install_requires", MAIN_GROUP), ("tests_require", "test")] {
+        let Some(value) = option(&contents, "options", key) else {
+            continue;
+        };
+        let reqs = if let Some(files) = value.trim().strip_prefix("file:") {
+            let mut reqs = Vec::new();
+            for file in files.split(',') {
+                reqs.extend(requirements::read_file(
+                    &dir.join(file.trim()),
+                    &mut BTreeSet::new(),
+                )?);
+            }
+            reqs
+        } else {
+            value.lines().filter_map(Requirement::parse).collect()
+        };
+        manifest.extend(group, reqs);
+    }
+    manifest.set_file(path);
+    Ok(manifest)
+}
+
+/// Get the raw value of `key` in `[section]`, joining any indented continuation lines.
+fn option(contents: &str, section: &str, key: &str) -> Option<String> {
+    let mut in_section = false;
+    let mut value: Option<String> = None;
+    for line in contents.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('#') || trimmed.starts_with(';') {
+            continue;
+        }
+        if let Some(value) = &mut value {
+            // Continuation lines are indented
+            if line.starts_with(char::is_whitespace) || trimmed.is_empty() {
+                if !trimmed.is_empty() {
+                    value.push('\n');
+                    value.push_str(trimmed);
+                }
+                continue;
+            }
+            break;
+        }
+        if let Some(name) = trimmed.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            in_section = name.trim() == section;
+        } else if in_section
+            && let Some((k, v)) = trimmed.split_once(['=', ':'])
+            && k.trim() == key
+        {
+            value = Some(v.trim().to_string());
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::manifest::names;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_read() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(
+            root.path().join("setup.cfg"),
+            r#"[metadata]
+name = example
+
+[options]
+packages = find:
+install_requires =
+    requests>=2.31
+    # http clients
+    importlib-metadata; python_version<"3.10"
+tests_require = file: requirements-test.txt
+
+[options.extras_require]
+dev = black
+"#,
+        )
+        .unwrap();
+        fs::write(root.path().join("requirements-test.txt"), "pytest\n").unwrap();
+        let manifest = read(&root.path().join("setup.cfg")).unwrap();
+        assert_eq!(
+            names(manifest.main().unwrap()),
+            ["requests", "importlib-metadata"]
+        );
+        assert_eq!(names(&manifest.groups["test"]), ["pytest"]);
+    }
+}
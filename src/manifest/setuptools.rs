@@ -0,0 +1,66 @@
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+use toml::Value;
+
+use super::{MAIN_GROUP, Manifest, ManifestReader, Pyproject, pep621::read_project, requirements};
+
+/// Reads projects built with setuptools, including dynamic dependencies.
+pub struct SetuptoolsReader;
+
+impl ManifestReader for SetuptoolsReader {
+    fn read(&self, pyproject: &Pyproject) -> Result<Manifest> {
+        let mut manifest = read_project(pyproject);
+        let dynamic = pyproject
+            .get("project.dynamic")
+            .and_then(Value::as_array)
+            .is_some_and(|fields| fields.iter().any(|f| f.as_str() == Some("dependencies")));
+        if !dynamic {
+            return Ok(manifest);
+        }
+
+        // `file` may be a single path or a list of paths
+        let files = match pyproject.get("tool.setuptools.dynamic.dependencies.file") {
+            Some(Value::String(file)) => vec![file.as_str()],
+            Some(Value::Array(files)) => files.iter().filter_map(Value::as_str).collect(),
+            _ => vec![],
+        };
+        for file in files {
+            let reqs = requirements::read_file(&pyproject.root.join(file), &mut BTreeSet::new())?;
+            manifest.extend(MAIN_GROUP, reqs);
+        }
+        Ok(manifest)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::manifest::names;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+
+    #[test]
+    fn test_read_dynamic() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(
+            root.path().join("requirements.in"),
+            "# runtime\nrequests>=2  # http\n--index-url https://example.com\n\nclick\n",
+        )
+        .unwrap();
+        let pyproject = Pyproject::parse(
+            root.path().to_path_buf(),
+            r#"
+[project]
+name = "example"
+dynamic = ["dependencies"]
+
+[tool.setuptools.dynamic]
+dependencies = { file = ["requirements.in"] }
+"#,
+        )
+        .unwrap();
+        let manifest = SetuptoolsReader.read(&pyproject).unwrap();
+        assert_eq!(names(manifest.main().unwrap()), ["requests", "click"]);
+    }
+}
@@ -0,0 +1,73 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use toml::Value;
+
+use crate::name::NormalizedName;
+
+use super::{Manifest, ManifestReader, Pyproject, pep621::read_project, requirement_array};
+
+/// Reads projects managed by uv.
+pub struct UvReader;
+
+impl ManifestReader for UvReader {
+    fn read(&self, pyproject: &Pyproject) -> Result<Manifest> {
+        let mut manifest = read_project(pyproject);
+        // Deprecated in favor of PEP 735 dependency groups, but still common
+        if let Some(reqs) = requirement_array(pyproject.get("tool.uv.dev-dependencies")) {
+            manifest.extend("dev", reqs);
+        }
+
+        // Sources override where a requirement comes from, e.g. `mylib = { path = "../mylib" }`
+        let sources = pyproject.get("tool.uv.sources").and_then(Value::as_table);
+        for (name, source) in sources.into_iter().flatten() {
+            let Some(path) = source.get("path").and_then(Value::as_str) else {
+                continue;
+            };
+            let name = NormalizedName::new(name);
+            for req in manifest.groups.values_mut().flatten() {
+                if req.normalized == name {
+                    req.path = Some(PathBuf::from(path));
+                }
+            }
+        }
+        Ok(manifest)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::manifest::Requirement;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_read() {
+        let pyproject = Pyproject::parse(
+            PathBuf::new(),
+            r#"
+[project]
+dependencies = ["fastapi"]
+
+[tool.uv]
+dev-dependencies = ["pytest"]
+
+[tool.uv.sources]
+fastapi = { path = "vendor/fastapi", editable = true }
+
+[dependency-groups]
+dev = ["ruff"]
+"#,
+        )
+        .unwrap();
+        let manifest = UvReader.read(&pyproject).unwrap();
+        assert_eq!(
+            manifest.main().unwrap(),
+            [Requirement::new("fastapi").with_path("vendor/fastapi")]
+        );
+        assert_eq!(
+            manifest.groups["dev"],
+            [Requirement::new("ruff"), Requirement::new("pytest")]
+        );
+    }
+}
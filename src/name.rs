@@ -0,0 +1,75 @@
+use std::{borrow::Borrow, fmt};
+
+/// A distribution name, normalized as described by PEP 503.
+///
+/// Runs of `-`, `_` and `.` collapse to a single `-`, and the result is lowercased, so `PyYAML`
+/// and `pyyaml` compare equal, as do `py_yaml` and `py-yaml`. Like PEP 503's
+/// `re.sub(r"[-_.]+", "-", name)`, separators at either end are kept, so the private module
+/// `_pytest` stays distinct from the `pytest` distribution. Anything shown to the user should
+/// keep the name as it was spelled instead.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NormalizedName(String);
+
+impl NormalizedName {
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        let mut out = String::with_capacity(name.len());
+        for c in name.trim().chars() {
+            if matches!(c, '-' | '_' | '.') {
+                if !out.ends_with('-') {
+                    out.push('-');
+                }
+            } else {
+                out.push(c.to_ascii_lowercase());
+            }
+        }
+        NormalizedName(out)
+    }
+
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for NormalizedName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Borrow<str> for NormalizedName {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(NormalizedName::new("PyYAML").as_str(), "pyyaml");
+        assert_eq!(NormalizedName::new("py_yaml").as_str(), "py-yaml");
+        assert_eq!(
+            NormalizedName::new("zope.interface").as_str(),
+            "zope-interface"
+        );
+        assert_eq!(NormalizedName::new("Foo__-.Bar").as_str(), "foo-bar");
+        assert_eq!(NormalizedName::new("PyYaml"), NormalizedName::new("pyyaml"));
+    }
+
+    #[test]
+    fn test_normalize_edges() {
+        // Separators at the edges are collapsed but kept, as PEP 503 does
+        assert_eq!(NormalizedName::new("_private").as_str(), "-private");
+        assert_eq!(NormalizedName::new("__init__").as_str(), "-init-");
+        assert_eq!(NormalizedName::new(" trailing. ").as_str(), "trailing-");
+        assert_ne!(
+            NormalizedName::new("_pytest"),
+            NormalizedName::new("pytest")
+        );
+    }
+}
@@ -11,23 +11,22 @@ use phf::phf_ordered_map;
 /// If you would like to add or improve this list, please file a PR:
 /// <https://github.com/lukehsiao/poetry-udeps>.
 ///
+/// Names are normalized as described by PEP 503 (lowercase, with `-` as the
+/// only separator). Entries are only needed when the module name differs from
+/// the normalized package name.
+///
 /// Please try to keep this list sorted lexicographically and wrapped to 79
 /// columns (inclusive).
 ///
 /// [1]: https://stackoverflow.com/a/54853084
 #[rustfmt::skip]
 pub static KNOWN_NAMES: phf::OrderedMap<&str, &str> = phf_ordered_map! {
-    "Flask" => "flask",
-    "Markdown" => "markdown",
-    "PyYAML" => "yaml",
-    "SQLAlchemy" => "sqlalchemy",
-    "Wand" => "wand",
     "argon2-cffi" => "argon2",
     "beautifulsoup4" => "bs4",
     "boto3-stubs" => "mypy_boto3_iam",
     "celery-redbeat" => "redbeat",
-    "databricks-sdk" => "databricks.sdk",
     "databricks-sql-connector" => "databricks-sql",
+    "dbt-core" => "dbt",
     "faiss-cpu" => "faiss",
     "faiss-gpu" => "faiss",
     "google-api-python-client" => "googleapiclient",
@@ -35,28 +34,103 @@ pub static KNOWN_NAMES: phf::OrderedMap<&str, &str> = phf_ordered_map! {
     "grpcio" => "grpc",
     "hydra-colorlog" => "colorlog",
     "hydra-core" => "hydra",
-    "json-stream" => "json_stream",
     "jupyter" => "IPython",
-    "levenshtein" => "Levenshtein",
     "opensearch-py" => "opensearchpy",
-    "pdfminer.six" => "pdfminer",
+    "opentelemetry-api" => "opentelemetry",
+    "opentelemetry-semantic-conventions" => "opentelemetry.semconv",
+    "pdfminer-six" => "pdfminer",
     "protobuf" => "google.protobuf",
     "pyautogen" => "autogen",
     "pybars3" => "pybars",
+    "pytest" => "_pytest",
     "python-jose" => "jose",
     "python-multipart" => "multipart",
     "python-slugify" => "slugify",
     "pytorch-nlp" => "torchnlp",
     "pyvespa" => "vespa",
+    "pyyaml" => "yaml",
     "scikit-learn" => "sklearn",
     "snowflake-connector-python" => "snowflake",
     "unicodedata2" => "unicodedata",
     "vl-convert-python" => "vl_convert",
 };
 
+/// Families of distributions which install into a namespace package, where the
+/// module path is not simply the distribution name.
+///
+/// Each entry maps a (normalized) distribution name prefix to the module path
+/// prefix it replaces, e.g. `dbt-postgres` provides `dbt.adapters.postgres`.
+/// Entries in [`KNOWN_NAMES`] take precedence.
+///
+/// Most namespace packages do not need an entry, as `google-cloud-storage`
+/// already matches `google.cloud.storage` once normalized.
+#[rustfmt::skip]
+pub static NAMESPACE_PREFIXES: phf::OrderedMap<&str, &str> = phf_ordered_map! {
+    "dbt-" => "dbt.adapters.",
+};
+
+/// The module a distribution is expected to provide, if it is not simply its
+/// own name.
+pub fn known_module(name: &str) -> Option<String> {
+    if let Some(module) = KNOWN_NAMES.get(name) {
+        return Some((*module).to_string());
+    }
+    NAMESPACE_PREFIXES.entries().find_map(|(prefix, module)| {
+        name.strip_prefix(prefix)
+            .map(|rest| format!("{module}{rest}"))
+    })
+}
+
+/// Describe the entry [`known_module`] used for a distribution, if any.
+pub fn known_entry(name: &str) -> Option<String> {
+    if let Some(module) = KNOWN_NAMES.get(name) {
+        return Some(format!("KNOWN_NAMES: \"{name}\" => \"{module}\""));
+    }
+    NAMESPACE_PREFIXES
+        .entries()
+        .find(|(prefix, _)| name.starts_with(**prefix))
+        .map(|(prefix, module)| format!("NAMESPACE_PREFIXES: \"{prefix}\" => \"{module}\""))
+}
+
 #[cfg(test)]
 mod tests {
-    use super::KNOWN_NAMES;
+    use super::{KNOWN_NAMES, known_entry, known_module};
+    use crate::name::NormalizedName;
+
+    #[test]
+    fn test_known_module() {
+        assert_eq!(known_module("pyyaml").as_deref(), Some("yaml"));
+        assert_eq!(known_module("dbt-core").as_deref(), Some("dbt"));
+        assert_eq!(
+            known_module("dbt-postgres").as_deref(),
+            Some("dbt.adapters.postgres")
+        );
+        assert_eq!(known_module("google-cloud-storage"), None);
+    }
+
+    #[test]
+    fn test_known_entry() {
+        assert_eq!(
+            known_entry("pyyaml").as_deref(),
+            Some(r#"KNOWN_NAMES: "pyyaml" => "yaml""#)
+        );
+        assert_eq!(
+            known_entry("dbt-postgres").as_deref(),
+            Some(r#"NAMESPACE_PREFIXES: "dbt-" => "dbt.adapters.""#)
+        );
+        assert_eq!(known_entry("requests"), None);
+    }
+
+    #[test]
+    fn known_names_are_normalized() {
+        for name in KNOWN_NAMES.keys() {
+            assert_eq!(
+                *name,
+                NormalizedName::new(name).as_str(),
+                r#""{name}" should be normalized in `KNOWN_NAMES`"#,
+            );
+        }
+    }
 
     #[test]
     fn known_names_are_sorted() {
@@ -1,18 +1,42 @@
+use std::fmt;
+
 use anyhow::Result;
 use nom::{
     IResult, Parser,
     branch::alt,
     bytes::complete::{is_not, tag, take_until},
-    character::complete::{alpha1, alphanumeric1, anychar, char, space1},
-    combinator::{all_consuming, map, recognize, value},
-    multi::{many0, many0_count},
+    character::complete::{alpha1, alphanumeric1, anychar, char, none_of, space1},
+    combinator::{map, opt, recognize, value},
+    multi::{many0_count, many1_count},
     sequence::pair,
 };
+use serde::{Deserialize, Serialize};
+
+/// The version of the parser, which is bumped whenever the imports parsed from a file may change.
+///
+/// This invalidates the imports cached by earlier versions.
+pub const VERSION: u32 = 2;
 
-#[derive(Debug, PartialEq)]
+/// An absolute import statement, e.g. `from google.cloud import bigquery`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct ImportStatement {
+    /// The module imported, or imported from, e.g. `google.cloud`.
     pub package: String,
+    /// The name imported from the module, e.g. `bigquery`, or empty for `import google.cloud`.
     pub module: String,
+    /// The line the statement starts on, counting from 1.
+    pub line: usize,
+}
+
+impl fmt::Display for ImportStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.module.is_empty() {
+            write!(f, "import {}", self.package)
+        } else {
+            write!(f, "from {} import {}", self.package, self.module)
+        }
+    }
 }
 
 /// Parsing identifiers that may start with a letter (or underscore) and may contain underscores,
@@ -39,6 +63,7 @@ fn from_package_import(input: &str) -> IResult<&str, ImportStatement> {
     let statement = ImportStatement {
         module: module.to_owned(),
         package: package.to_owned(),
+        line: 0,
     };
     Ok((input, statement))
 }
@@ -48,24 +73,64 @@ fn simple_import(input: &str) -> IResult<&str, ImportStatement> {
     let statement = ImportStatement {
         module: String::new(),
         package: package.to_owned(),
+        line: 0,
     };
     Ok((input, statement))
 }
 
+/// Relative imports, like `from . import x`, only import the project's own modules.
+fn relative_import(input: &str) -> IResult<&str, ()> {
+    value(
+        (),
+        (
+            tag("from"),
+            space1,
+            many1_count(char('.')),
+            opt(identifier),
+            space1,
+            tag("import"),
+            space1,
+            identifier,
+        ),
+    )
+    .parse(input)
+}
+
 fn inline_comment(input: &str) -> IResult<&str, ()> {
     value((), pair(char('#'), is_not("\n\r"))).parse(input)
 }
 
 fn multiline_comment(input: &str) -> IResult<&str, ()> {
-    value((), (tag("\"\"\""), take_until("\"\"\""), tag("\"\"\""))).parse(input)
+    let block = |quotes| (tag(quotes), take_until(quotes), tag(quotes));
+    value((), alt((block("\"\"\""), block("'''")))).parse(input)
+}
+
+/// A string on a single line, which may escape its quote with a backslash.
+fn string_literal(input: &str) -> IResult<&str, ()> {
+    let contents = |special| {
+        many0_count(alt((
+            value((), pair(char('\\'), anychar)),
+            value((), none_of(special)),
+        )))
+    };
+    value(
+        (),
+        alt((
+            (char('"'), contents("\"\\\n"), char('"')),
+            (char('\''), contents("'\\\n"), char('\'')),
+        )),
+    )
+    .parse(input)
 }
 
 fn parse_block(input: &str) -> IResult<&str, Option<ImportStatement>> {
     alt((
         map(from_package_import, Some),
+        map(relative_import, |()| None),
         map(simple_import, Some),
         map(inline_comment, |()| None),
         map(multiline_comment, |()| None),
+        map(string_literal, |()| None),
         // Consume everything else
         map(anychar, |_| None),
     ))
@@ -73,8 +138,22 @@ fn parse_block(input: &str) -> IResult<&str, Option<ImportStatement>> {
 }
 
 fn parse_file(input: &str) -> IResult<&str, Vec<ImportStatement>> {
-    let (i, v) = all_consuming(many0(parse_block)).parse(input)?;
-    Ok((i, v.into_iter().flatten().collect()))
+    let mut imports = Vec::new();
+    let mut rest = input;
+    // Lines are counted as we go, up to the start of the latest import
+    let (mut line, mut counted) = (1, 0);
+    while !rest.is_empty() {
+        let (next, block) = parse_block(rest)?;
+        if let Some(mut import) = block {
+            let start = input.len() - rest.len();
+            line += input[counted..start].matches('\n').count();
+            counted = start;
+            import.line = line;
+            imports.push(import);
+        }
+        rest = next;
+    }
+    Ok((rest, imports))
 }
 
 pub fn parse_python_file(input: &str) -> Result<Vec<ImportStatement>> {
@@ -158,10 +237,54 @@ mod test {
         );
     }
     #[test]
+    fn test_single_quoted_multiline_comment() {
+        assert_eq!(
+            multiline_comment("'''Helpers to import data from files.'''"),
+            Ok(("", ()))
+        );
+        assert_eq!(
+            parse_file("'''\nHelpers to import data from files.\n'''\nimport csv\n")
+                .unwrap()
+                .1
+                .iter()
+                .map(|i| (i.package.as_str(), i.line))
+                .collect::<Vec<_>>(),
+            [("csv", 4)]
+        );
+    }
+    #[test]
+    fn test_string_literal() {
+        assert_eq!(string_literal(r#""import os" + x"#), Ok((" + x", ())));
+        assert_eq!(string_literal(r"'it\'s from here' + x"), Ok((" + x", ())));
+        assert_eq!(string_literal("''"), Ok(("", ())));
+        assert!(string_literal("'unterminated\nimport os").is_err());
+        let file = r#"
+    message = "data is imported from files"
+    other = 'then import it, "quoted"'
+    import json
+            "#;
+        assert_eq!(
+            parse_file(file)
+                .unwrap()
+                .1
+                .iter()
+                .map(|i| i.package.as_str())
+                .collect::<Vec<_>>(),
+            ["json"]
+        );
+    }
+    #[test]
     fn test_inline_comment() {
         assert_eq!(inline_comment("# something else"), Ok(("", ())));
         assert_eq!(inline_comment("# ##### other stuff"), Ok(("", ())));
     }
+    #[test]
+    fn test_relative_import() {
+        assert_eq!(relative_import("from . import utils"), Ok(("", ())));
+        assert_eq!(relative_import("from ..models import User"), Ok(("", ())));
+        assert!(relative_import("from models import User").is_err());
+        assert_eq!(parse_file("from .requests import get").unwrap().1, []);
+    }
 
     #[test]
     fn test_parse_file() {
@@ -190,5 +313,9 @@ mod test {
             imports.iter().map(|i| &i.module).collect::<Vec<_>>(),
             ["pprint", "", "DEFAULT_COPY_TOKEN"]
         );
+        assert_eq!(
+            imports.iter().map(|i| i.line).collect::<Vec<_>>(),
+            [2, 3, 4]
+        );
     }
 }
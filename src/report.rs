@@ -0,0 +1,1008 @@
+//! The findings reported for a project, and the formats they can be printed in.
+
+use std::{
+    env,
+    fmt::{self, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::manifest::{Location, MAIN_GROUP, Suppression};
+use crate::name::NormalizedName;
+
+/// The version of the JSON output schema.
+///
+/// This is bumped whenever a field is removed or changes meaning, or a kind of finding is added.
+/// New fields may be added without bumping it.
+///
+/// Version 2 added the `missing`, `misplaced` and `stale-ignore` kinds, and made `group`
+/// nullable for them. Version 3 added the findings about dependencies ignored by a comment where
+/// they are declared, which carry the `suppression`.
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// How findings are printed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+#[non_exhaustive]
+pub enum Format {
+    /// The unused dependencies, or every finding with `--all-findings`, one per line, with where
+    /// it is.
+    #[default]
+    Text,
+    /// Every finding, in a versioned JSON schema.
+    Json,
+    /// Every finding, as a SARIF 2.1.0 log for code scanning tools.
+    Sarif,
+    /// The unused dependencies, or every finding with `--all-findings`, as GitHub Actions
+    /// workflow commands annotating the declarations.
+    Github,
+    /// A `JUnit` XML report, with one test case per checked dependency.
+    Junit,
+}
+
+/// The kinds of problems found with a dependency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum FindingKind {
+    /// The dependency is never imported.
+    Unused,
+    /// The dependency is not imported, but another used dependency requires it according to
+    /// poetry.lock, so it need not be declared directly.
+    Transitive,
+    /// A third-party module is imported, but no declared dependency provides it.
+    Missing,
+    /// The dependency is in the wrong group: a main dependency only imported by tests or other
+    /// code outside the project's packages, or a dev dependency imported by the package code.
+    Misplaced,
+    /// A package is listed in .poetryudepsignore, but is neither declared nor imported, or is
+    /// used, so ignoring it only risks hiding a problem later.
+    StaleIgnore,
+}
+
+impl FindingKind {
+    const ALL: [FindingKind; 5] = [
+        FindingKind::Unused,
+        FindingKind::Missing,
+        FindingKind::Transitive,
+        FindingKind::Misplaced,
+        FindingKind::StaleIgnore,
+    ];
+
+    /// The ID of the rule reporting this kind of finding.
+    #[must_use]
+    pub fn rule_id(self) -> &'static str {
+        match self {
+            FindingKind::Unused => "unused",
+            FindingKind::Transitive => "transitive",
+            FindingKind::Missing => "missing",
+            FindingKind::Misplaced => "misplaced",
+            FindingKind::StaleIgnore => "stale-ignore",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            FindingKind::Unused => "A declared dependency is never imported.",
+            FindingKind::Transitive => {
+                "A declared dependency is not imported, but is required by another used dependency."
+            }
+            FindingKind::Missing => {
+                "A third-party module is imported, but no declared dependency provides it."
+            }
+            FindingKind::Misplaced => {
+                "A dependency is declared in the wrong group for where it is imported."
+            }
+            FindingKind::StaleIgnore => {
+                "An ignored package is neither declared nor imported, or is used."
+            }
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            FindingKind::Unused => "Unused dependency",
+            FindingKind::Transitive => "Transitive dependency",
+            FindingKind::Missing => "Missing dependency",
+            FindingKind::Misplaced => "Misplaced dependency",
+            FindingKind::StaleIgnore => "Stale ignore entry",
+        }
+    }
+
+    /// Whether findings of this kind fail a run, rather than being notes.
+    #[must_use]
+    pub fn fails(self) -> bool {
+        self.level() == "warning"
+    }
+
+    /// The SARIF level of findings of this kind.
+    fn level(self) -> &'static str {
+        match self {
+            FindingKind::Unused | FindingKind::Missing | FindingKind::Misplaced => "warning",
+            FindingKind::Transitive | FindingKind::StaleIgnore => "note",
+        }
+    }
+}
+
+/// A problem found with a declared dependency.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct Finding {
+    pub kind: FindingKind,
+    /// The dependency, as spelled in the manifest.
+    pub package: String,
+    /// The PEP 503 normalized name of the dependency.
+    pub normalized: String,
+    /// The dependency group it is declared in, or `None` for [`FindingKind::Missing`] and for a
+    /// [`FindingKind::StaleIgnore`] package which is not declared.
+    pub group: Option<String>,
+    /// Where the dependency is declared, if known.
+    ///
+    /// For [`FindingKind::Missing`], this is the first place the module is imported instead, and
+    /// for [`FindingKind::StaleIgnore`], the line listing the package in .poetryudepsignore.
+    pub location: Option<Location>,
+    /// The module names which would have credited the dependency.
+    pub aliases: Vec<String>,
+    /// For [`FindingKind::Transitive`], the chain of packages requiring the dependency.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub required_by: Vec<String>,
+    /// In a workspace, the directory of the project the finding is about, relative to the
+    /// workspace root.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project: Option<PathBuf>,
+    /// The comment ignoring the dependency where it is declared, for the findings in
+    /// [`Report::suppressed`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suppression: Option<Suppression>,
+}
+
+impl Finding {
+    /// A finding of `kind` about `package`, declared in `group`, with no location, aliases or
+    /// requiring packages.
+    #[must_use]
+    pub fn new(kind: FindingKind, package: impl Into<String>, group: Option<String>) -> Self {
+        let package = package.into();
+        Finding {
+            kind,
+            normalized: NormalizedName::new(&package).to_string(),
+            package,
+            group,
+            location: None,
+            aliases: Vec::new(),
+            required_by: Vec::new(),
+            project: None,
+            suppression: None,
+        }
+    }
+
+    /// A sentence describing the finding.
+    #[must_use]
+    pub fn message(&self) -> String {
+        let package = &self.package;
+        let group = self.group.as_deref().unwrap_or_default();
+        match self.kind {
+            FindingKind::Unused => format!("{package} (group: {group}) is unused"),
+            FindingKind::Transitive => format!(
+                "{package} is not imported, but is required by {}",
+                self.required_by.join(" -> ")
+            ),
+            FindingKind::Missing => {
+                format!("{package} is imported, but not declared as a dependency")
+            }
+            FindingKind::Misplaced if group == MAIN_GROUP => {
+                format!(
+                    "{package} is only imported by tests or other non-package code, so it could be a dev dependency"
+                )
+            }
+            FindingKind::Misplaced => format!(
+                "{package} is imported by the project's code, but only declared in the {group} group"
+            ),
+            FindingKind::StaleIgnore if self.group.is_some() => {
+                format!("{package} is listed in .poetryudepsignore, but is used")
+            }
+            FindingKind::StaleIgnore => format!(
+                "{package} is listed in .poetryudepsignore, but is neither declared nor imported"
+            ),
+        }
+    }
+}
+
+/// An import which credited a dependency.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Usage {
+    /// Where the import is, relative to the project root.
+    pub location: Location,
+    /// The import statement, e.g. `from yaml import safe_load`.
+    pub statement: String,
+    /// The dependency whose code contains the import, or `None` for project code.
+    pub owner: Option<String>,
+}
+
+impl Usage {
+    #[must_use]
+    pub fn new(location: Location, statement: impl Into<String>, owner: Option<String>) -> Self {
+        Usage {
+            location,
+            statement: statement.into(),
+            owner,
+        }
+    }
+}
+
+/// A declared dependency which was checked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Checked {
+    /// The dependency, as spelled in the manifest.
+    pub package: String,
+    /// The dependency group it is declared in.
+    pub group: Option<String>,
+    /// Where the dependency is declared, if known.
+    pub location: Option<Location>,
+    /// Whether the dependency is listed in .poetryudepsignore, or ignored by a comment where it is
+    /// declared, so was not reported.
+    pub ignored: bool,
+    /// The comment ignoring the dependency where it is declared, with the reason it gives.
+    pub suppression: Option<Suppression>,
+    /// The imports which credited the dependency, in the project and in other dependencies.
+    pub usages: Vec<Usage>,
+    /// In a workspace, the directory of the project declaring the dependency.
+    pub project: Option<PathBuf>,
+}
+
+impl Checked {
+    /// A dependency declared in `group`, which is not ignored and credited by no imports.
+    #[must_use]
+    pub fn new(package: impl Into<String>, group: Option<String>) -> Self {
+        Checked {
+            package: package.into(),
+            group,
+            location: None,
+            ignored: false,
+            suppression: None,
+            usages: Vec::new(),
+            project: None,
+        }
+    }
+}
+
+/// A project file which could not be scanned as it should have been.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FileWarning {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+impl FileWarning {
+    #[must_use]
+    pub fn new(path: impl Into<PathBuf>, message: impl Into<String>) -> Self {
+        FileWarning {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for FileWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.message)
+    }
+}
+
+/// Counts describing how much work a run did.
+///
+/// Use [`Statistics::default`] to construct them, with every count at zero.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Statistics {
+    /// The project files scanned for imports.
+    pub files: usize,
+    /// The files installed by dependencies scanned for imports, with a virtualenv.
+    pub dependency_files: usize,
+    /// The imports found in every file scanned.
+    pub imports: usize,
+    /// The files scanned whose imports were cached by an earlier run.
+    pub cached: usize,
+    /// The wall-clock time the run took.
+    pub elapsed: Duration,
+}
+
+impl std::ops::AddAssign for Statistics {
+    fn add_assign(&mut self, other: Statistics) {
+        self.files += other.files;
+        self.dependency_files += other.dependency_files;
+        self.imports += other.imports;
+        self.cached += other.cached;
+        self.elapsed += other.elapsed;
+    }
+}
+
+/// The dependencies checked, and the problems found with them.
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct Report {
+    pub checked: Vec<Checked>,
+    pub findings: Vec<Finding>,
+    /// The findings about dependencies ignored by a comment where they are declared.
+    ///
+    /// They never fail a run, and are only rendered as JSON and SARIF, marked as suppressed.
+    pub suppressed: Vec<Finding>,
+    /// The project files which were skipped, or only partly understood.
+    pub warnings: Vec<FileWarning>,
+    pub statistics: Statistics,
+    /// The project root, or workspace root, which the paths in the report are relative to.
+    pub root: PathBuf,
+}
+
+impl Report {
+    /// An empty report, whose paths are relative to `root`.
+    #[must_use]
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Report {
+            root: root.into(),
+            ..Report::default()
+        }
+    }
+
+    /// The unused dependencies, which `--fix` removes.
+    pub fn unused(&self) -> impl Iterator<Item = &Finding> {
+        self.findings
+            .iter()
+            .filter(|finding| finding.kind == FindingKind::Unused)
+    }
+
+    /// Whether any finding fails the run, i.e. an unused, missing or misplaced dependency.
+    #[must_use]
+    pub fn fails(&self) -> bool {
+        self.findings.iter().any(|finding| finding.kind.fails())
+    }
+
+    /// Drop every finding but the unused dependencies, which are all a run reports unless asked
+    /// for every kind of finding.
+    pub fn retain_unused(&mut self) {
+        self.findings
+            .retain(|finding| finding.kind == FindingKind::Unused);
+    }
+
+    /// Add the report of a project in a workspace, at `project` relative to the workspace root.
+    ///
+    /// The paths in the project's report are made relative to the workspace root too.
+    pub fn merge(&mut self, project: &Path, mut other: Report) {
+        let within = |file: &Path| relative_to(project, file);
+        for finding in other.findings.iter_mut().chain(&mut other.suppressed) {
+            finding.project = Some(project.to_path_buf());
+            if let Some(location) = &mut finding.location {
+                location.file = within(&location.file);
+            }
+        }
+        for checked in &mut other.checked {
+            checked.project = Some(project.to_path_buf());
+            if let Some(location) = &mut checked.location {
+                location.file = within(&location.file);
+            }
+            for usage in &mut checked.usages {
+                usage.location.file = within(&usage.location.file);
+            }
+        }
+        for warning in &mut other.warnings {
+            warning.path = within(&warning.path);
+        }
+        self.checked.append(&mut other.checked);
+        self.findings.append(&mut other.findings);
+        self.suppressed.append(&mut other.suppressed);
+        self.warnings.append(&mut other.warnings);
+        self.statistics += other.statistics;
+    }
+
+    /// Render the report in `format`.
+    ///
+    /// The `text` format lists every finding, one per line. Only the `json` and `sarif` formats
+    /// include the suppressed findings.
+    #[allow(clippy::missing_errors_doc)]
+    pub fn render(&self, format: Format) -> serde_json::Result<String> {
+        let all = || -> Vec<Finding> {
+            self.findings
+                .iter()
+                .chain(&self.suppressed)
+                .cloned()
+                .collect()
+        };
+        Ok(match format {
+            Format::Text => self
+                .findings
+                .iter()
+                .fold(String::new(), |mut out, finding| {
+                    let _ = writeln!(out, "{finding}");
+                    out
+                }),
+            Format::Json => to_json(&all())? + "\n",
+            Format::Sarif => to_sarif(&all())? + "\n",
+            Format::Github => to_github(&self.findings, &self.root),
+            Format::Junit => to_junit(self),
+        })
+    }
+}
+
+/// Join `file` to the `project` directory, leaving the current directory implicit.
+fn relative_to(project: &Path, file: &Path) -> PathBuf {
+    let path = project.join(file);
+    path.strip_prefix(".")
+        .map_or(path.clone(), Path::to_path_buf)
+}
+
+/// Where the finding is, if known, followed by its message.
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(location) = &self.location {
+            write!(f, "{location}: ")?;
+        }
+        f.write_str(&self.message())
+    }
+}
+
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    version: u32,
+    findings: &'a [Finding],
+}
+
+/// Render findings as a JSON document.
+#[allow(clippy::missing_errors_doc)]
+pub fn to_json(findings: &[Finding]) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(&JsonReport {
+        version: SCHEMA_VERSION,
+        findings,
+    })
+}
+
+/// Render findings as a SARIF 2.1.0 log, with one rule per kind of finding.
+///
+/// The findings ignored by a comment are marked as suppressed in source, with the reason given.
+#[allow(clippy::missing_errors_doc)]
+pub fn to_sarif(findings: &[Finding]) -> serde_json::Result<String> {
+    let rules: Vec<_> = FindingKind::ALL
+        .iter()
+        .map(|kind| {
+            json!({
+                "id": kind.rule_id(),
+                "shortDescription": { "text": kind.description() },
+                "defaultConfiguration": { "level": kind.level() },
+            })
+        })
+        .collect();
+    let results: Vec<_> = findings
+        .iter()
+        .map(|finding| {
+            let rule_index = FindingKind::ALL.iter().position(|k| *k == finding.kind);
+            let mut result = json!({
+                "ruleId": finding.kind.rule_id(),
+                "ruleIndex": rule_index,
+                "level": finding.kind.level(),
+                "message": { "text": finding.message() },
+            });
+            if let Some(location) = &finding.location {
+                let mut physical = json!({
+                    "artifactLocation": {
+                        "uri": location.file.to_string_lossy().replace('\\', "/"),
+                    },
+                });
+                if let Some(line) = location.line {
+                    physical["region"] = json!({ "startLine": line });
+                    if let Some(column) = location.column {
+                        physical["region"]["startColumn"] = json!(column);
+                    }
+                }
+                result["locations"] = json!([{ "physicalLocation": physical }]);
+            }
+            if let Some(suppression) = &finding.suppression {
+                let mut entry = json!({ "kind": "inSource" });
+                if let Some(reason) = &suppression.reason {
+                    entry["justification"] = json!(reason);
+                }
+                result["suppressions"] = json!([entry]);
+            }
+            result
+        })
+        .collect();
+    serde_json::to_string_pretty(&json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": env!("CARGO_PKG_HOMEPAGE"),
+                    "rules": rules,
+                },
+            },
+            "results": results,
+        }],
+    }))
+}
+
+/// Render findings as GitHub Actions workflow commands, which annotate the declarations.
+///
+/// GitHub expects paths relative to the repository root, which is the current directory of a
+/// workflow step, so the paths of findings, relative to the project `root`, are made relative to
+/// the current directory.
+#[must_use]
+pub fn to_github(findings: &[Finding], root: &Path) -> String {
+    let cwd = env::current_dir().unwrap_or_default();
+    let mut out = String::new();
+    for finding in findings {
+        let command = match finding.kind.level() {
+            "note" => "notice",
+            level => level,
+        };
+        let mut properties = Vec::new();
+        if let Some(location) = &finding.location {
+            let file = relative_to(root, &location.file);
+            let file = file.strip_prefix(&cwd).unwrap_or(&file);
+            let file = file.to_string_lossy().replace('\\', "/");
+            properties.push(format!("file={}", escape_property(&file)));
+            if let Some(line) = location.line {
+                properties.push(format!("line={line}"));
+            }
+            if let Some(column) = location.column {
+                properties.push(format!("col={column}"));
+            }
+        }
+        properties.push(format!("title={}", escape_property(finding.kind.title())));
+        let _ = writeln!(
+            out,
+            "::{command} {}::{}",
+            properties.join(","),
+            escape_data(&finding.message())
+        );
+    }
+    out
+}
+
+/// Escape the message of a workflow command.
+fn escape_data(data: &str) -> String {
+    data.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escape a property of a workflow command.
+fn escape_property(property: &str) -> String {
+    escape_data(property)
+        .replace(':', "%3A")
+        .replace(',', "%2C")
+}
+
+/// A `JUnit` test case: a checked dependency, or a module imported without one.
+struct Case<'a> {
+    project: Option<&'a Path>,
+    package: &'a str,
+    group: Option<&'a str>,
+    ignored: bool,
+    suppression: Option<&'a Suppression>,
+    findings: Vec<&'a Finding>,
+}
+
+impl Case<'_> {
+    fn failed(&self) -> bool {
+        self.findings.iter().any(|f| f.kind.level() == "warning")
+    }
+}
+
+/// Render a `JUnit` XML report, with one test case per checked dependency.
+///
+/// A test case fails if a warning was found for the dependency, and is skipped if the dependency
+/// is ignored. Modules imported without a declared dependency are added as failed test cases too.
+/// In a workspace, there is one test suite per project.
+#[must_use]
+pub fn to_junit(report: &Report) -> String {
+    let found = |checked: &Checked, finding: &&Finding| {
+        finding.package == checked.package
+            && finding.group == checked.group
+            && finding.project == checked.project
+    };
+    let mut cases = Vec::new();
+    for checked in &report.checked {
+        cases.push(Case {
+            project: checked.project.as_deref(),
+            package: &checked.package,
+            group: checked.group.as_deref(),
+            ignored: checked.ignored,
+            suppression: checked.suppression.as_ref(),
+            findings: report
+                .findings
+                .iter()
+                .filter(|finding| found(checked, finding))
+                .collect(),
+        });
+    }
+    for finding in &report.findings {
+        if !report
+            .checked
+            .iter()
+            .any(|checked| found(checked, &finding))
+        {
+            cases.push(Case {
+                project: finding.project.as_deref(),
+                package: &finding.package,
+                group: None,
+                ignored: false,
+                suppression: None,
+                findings: vec![finding],
+            });
+        }
+    }
+    // Group the cases by project, keeping the order projects were first seen in
+    let mut suites: Vec<(Option<&Path>, Vec<Case>)> = Vec::new();
+    for case in cases {
+        match suites
+            .iter_mut()
+            .find(|(project, _)| *project == case.project)
+        {
+            Some((_, suite)) => suite.push(case),
+            None => suites.push((case.project, vec![case])),
+        }
+    }
+
+    let totals = |cases: &[Case]| {
+        (
+            cases.len(),
+            cases.iter().filter(|case| case.failed()).count(),
+            cases.iter().filter(|case| case.ignored).count(),
+        )
+    };
+    let all: Vec<_> = suites.iter().map(|(_, cases)| totals(cases)).collect();
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        out,
+        "<testsuites name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\">",
+        env!("CARGO_PKG_NAME"),
+        all.iter().map(|t| t.0).sum::<usize>(),
+        all.iter().map(|t| t.1).sum::<usize>(),
+        all.iter().map(|t| t.2).sum::<usize>(),
+    );
+    if suites.is_empty() {
+        suites.push((None, Vec::new()));
+    }
+    for (project, cases) in suites {
+        let (tests, failures, skipped) = totals(&cases);
+        let name = project.map_or(env!("CARGO_PKG_NAME").into(), Path::to_string_lossy);
+        let _ = writeln!(
+            out,
+            "  <testsuite name=\"{}\" tests=\"{tests}\" failures=\"{failures}\" skipped=\"{skipped}\">",
+            escape_xml(&name),
+        );
+        for case in cases {
+            write_case(&mut out, case);
+        }
+        out.push_str("  </testsuite>\n");
+    }
+    out.push_str("</testsuites>\n");
+    out
+}
+
+/// Write a `JUnit` test case.
+fn write_case(out: &mut String, case: Case) {
+    let _ = write!(
+        out,
+        "    <testcase name=\"{}\" classname=\"{}\"",
+        escape_xml(case.package),
+        escape_xml(case.group.unwrap_or("undeclared")),
+    );
+    if !case.ignored && case.findings.is_empty() {
+        out.push_str("/>\n");
+        return;
+    }
+    out.push_str(">\n");
+    if case.ignored {
+        let message = case.suppression.map_or_else(
+            || "listed in .poetryudepsignore".into(),
+            ToString::to_string,
+        );
+        let _ = writeln!(out, "      <skipped message=\"{}\"/>", escape_xml(&message));
+    }
+    for finding in case.findings {
+        let message = escape_xml(&finding.message());
+        if finding.kind.level() == "warning" {
+            let location = finding.location.as_ref().map(ToString::to_string);
+            let _ = writeln!(
+                out,
+                "      <failure type=\"{}\" message=\"{message}\">{}</failure>",
+                finding.kind.rule_id(),
+                escape_xml(&location.unwrap_or_default()),
+            );
+        } else {
+            let _ = writeln!(out, "      <system-out>{message}</system-out>");
+        }
+    }
+    out.push_str("    </testcase>\n");
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::{path::Path, slice};
+
+    #[test]
+    fn test_to_json() {
+        let findings = [
+            Finding {
+                kind: FindingKind::Unused,
+                package: "PyYAML".to_string(),
+                normalized: "pyyaml".to_string(),
+                group: Some("main".to_string()),
+                location: Some(Location::file(Path::new("./pyproject.toml"))),
+                aliases: vec!["PyYAML".to_string(), "yaml".to_string()],
+                required_by: Vec::new(),
+                project: None,
+                suppression: None,
+            },
+            Finding {
+                kind: FindingKind::Transitive,
+                package: "greenlet".to_string(),
+                normalized: "greenlet".to_string(),
+                group: Some("main".to_string()),
+                location: None,
+                aliases: vec!["greenlet".to_string()],
+                required_by: vec!["sqlalchemy".to_string(), "greenlet".to_string()],
+                project: None,
+                suppression: Some(Suppression {
+                    reason: Some("loaded by sqlalchemy".to_string()),
+                }),
+            },
+        ];
+        assert_eq!(
+            to_json(&findings).unwrap(),
+            r#"{
+  "version": 3,
+  "findings": [
+    {
+      "kind": "unused",
+      "package": "PyYAML",
+      "normalized": "pyyaml",
+      "group": "main",
+      "location": {
+        "file": "pyproject.toml",
+        "line": null,
+        "column": null
+      },
+      "aliases": [
+        "PyYAML",
+        "yaml"
+      ]
+    },
+    {
+      "kind": "transitive",
+      "package": "greenlet",
+      "normalized": "greenlet",
+      "group": "main",
+      "location": null,
+      "aliases": [
+        "greenlet"
+      ],
+      "required_by": [
+        "sqlalchemy",
+        "greenlet"
+      ],
+      "suppression": {
+        "reason": "loaded by sqlalchemy"
+      }
+    }
+  ]
+}"#
+        );
+        // Kinds are named like the SARIF rules
+        for kind in FindingKind::ALL {
+            assert_eq!(serde_json::to_value(kind).unwrap(), kind.rule_id());
+        }
+    }
+
+    #[test]
+    fn test_to_sarif() {
+        let mut location = Location::file(Path::new("./src/app.py"));
+        location.line = Some(3);
+        let findings = [
+            Finding {
+                kind: FindingKind::Missing,
+                package: "numpy".to_string(),
+                normalized: "numpy".to_string(),
+                group: None,
+                location: Some(location),
+                aliases: Vec::new(),
+                required_by: Vec::new(),
+                project: None,
+                suppression: None,
+            },
+            Finding {
+                kind: FindingKind::Misplaced,
+                package: "pytest-mock".to_string(),
+                normalized: "pytest-mock".to_string(),
+                group: Some("main".to_string()),
+                location: None,
+                aliases: Vec::new(),
+                required_by: Vec::new(),
+                project: None,
+                suppression: Some(Suppression {
+                    reason: Some("a pytest plugin".to_string()),
+                }),
+            },
+        ];
+        let sarif: serde_json::Value = serde_json::from_str(&to_sarif(&findings).unwrap()).unwrap();
+        assert_eq!(sarif["version"], "2.1.0");
+        let run = &sarif["runs"][0];
+        let rules: Vec<&str> = run["tool"]["driver"]["rules"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|rule| rule["id"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            rules,
+            [
+                "unused",
+                "missing",
+                "transitive",
+                "misplaced",
+                "stale-ignore"
+            ]
+        );
+
+        let missing = &run["results"][0];
+        assert_eq!(missing["ruleId"], "missing");
+        assert_eq!(missing["ruleIndex"], 1);
+        assert_eq!(
+            missing["message"]["text"],
+            "numpy is imported, but not declared as a dependency"
+        );
+        let physical = &missing["locations"][0]["physicalLocation"];
+        assert_eq!(physical["artifactLocation"]["uri"], "src/app.py");
+        assert_eq!(physical["region"]["startLine"], 3);
+        assert!(missing.get("suppressions").is_none());
+
+        let misplaced = &run["results"][1];
+        assert_eq!(
+            misplaced["message"]["text"],
+            "pytest-mock is only imported by tests or other non-package code, so it could be a dev dependency"
+        );
+        assert!(misplaced.get("locations").is_none());
+        assert_eq!(
+            misplaced["suppressions"],
+            json!([{ "kind": "inSource", "justification": "a pytest plugin" }])
+        );
+    }
+
+    fn unused(package: &str, line: Option<usize>) -> Finding {
+        let mut location = Location::file(Path::new("pyproject.toml"));
+        location.line = line;
+        Finding {
+            kind: FindingKind::Unused,
+            package: package.to_string(),
+            normalized: package.to_string(),
+            group: Some("main".to_string()),
+            location: Some(location),
+            aliases: vec![package.to_string()],
+            required_by: Vec::new(),
+            project: None,
+            suppression: None,
+        }
+    }
+
+    #[test]
+    fn test_render_text() {
+        let mut stale = unused("flask", Some(3));
+        stale.kind = FindingKind::StaleIgnore;
+        stale.group = None;
+        stale.location = Some(Location::file(Path::new(".poetryudepsignore")));
+        let mut missing = unused("numpy", None);
+        missing.kind = FindingKind::Missing;
+        missing.group = None;
+        missing.location = Some(Location::file(Path::new("app.py")));
+        let mut report = Report {
+            findings: vec![stale, missing],
+            ..Report::default()
+        };
+        assert_eq!(
+            report.render(Format::Text).unwrap(),
+            ".poetryudepsignore: flask is listed in .poetryudepsignore, but is neither declared nor imported
+app.py: numpy is imported, but not declared as a dependency
+"
+        );
+        assert!(report.fails());
+        // Notes alone do not fail a run
+        report.findings.pop();
+        assert!(!report.fails());
+    }
+
+    #[test]
+    fn test_to_github() {
+        let mut transitive = unused("greenlet", None);
+        transitive.kind = FindingKind::Transitive;
+        transitive.required_by = vec!["sqlalchemy".to_string(), "greenlet".to_string()];
+        assert_eq!(
+            to_github(&[unused("requests", Some(42)), transitive], Path::new("")),
+            "::warning file=pyproject.toml,line=42,title=Unused dependency::\
+             requests (group: main) is unused
+::notice file=pyproject.toml,title=Transitive dependency::\
+             greenlet is not imported, but is required by sqlalchemy -> greenlet
+"
+        );
+        // Paths are relative to the current directory, rather than the project
+        let finding = unused("requests", Some(42));
+        let cwd = env::current_dir().unwrap();
+        for root in [Path::new("./backend"), &cwd.join("backend")] {
+            assert!(
+                to_github(slice::from_ref(&finding), root)
+                    .starts_with("::warning file=backend/pyproject.toml,line=42,")
+            );
+        }
+        assert_eq!(escape_property("a:b,c%\n"), "a%3Ab%2Cc%25%0A");
+    }
+
+    #[test]
+    fn test_to_junit() {
+        let checked = |package: &str, ignored| Checked {
+            package: package.to_string(),
+            group: Some("main".to_string()),
+            location: None,
+            ignored,
+            suppression: None,
+            usages: Vec::new(),
+            project: None,
+        };
+        let mut missing = unused("numpy", Some(3));
+        missing.kind = FindingKind::Missing;
+        missing.group = None;
+        missing.location = Some(Location::file(Path::new("app.py")));
+        let report = Report {
+            checked: vec![
+                checked("requests", false),
+                checked("asyncpg", true),
+                checked("click", false),
+                Checked {
+                    suppression: Some(Suppression {
+                        reason: Some("loaded by <plugins>".to_string()),
+                    }),
+                    ..checked("psycopg", true)
+                },
+            ],
+            findings: vec![unused("requests", Some(42)), missing],
+            ..Report::default()
+        };
+        assert_eq!(
+            to_junit(&report),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="poetry-udeps" tests="5" failures="2" skipped="2">
+  <testsuite name="poetry-udeps" tests="5" failures="2" skipped="2">
+    <testcase name="requests" classname="main">
+      <failure type="unused" message="requests (group: main) is unused">pyproject.toml:42</failure>
+    </testcase>
+    <testcase name="asyncpg" classname="main">
+      <skipped message="listed in .poetryudepsignore"/>
+    </testcase>
+    <testcase name="click" classname="main"/>
+    <testcase name="psycopg" classname="main">
+      <skipped message="ignored: loaded by &lt;plugins&gt;"/>
+    </testcase>
+    <testcase name="numpy" classname="undeclared">
+      <failure type="missing" message="numpy is imported, but not declared as a dependency">app.py</failure>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#
+        );
+    }
+}
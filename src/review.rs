@@ -0,0 +1,334 @@
+//! Reviewing unused dependencies one at a time in the terminal.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt, fs,
+    io::{self, BufRead, Write},
+    path::Path,
+};
+
+use toml::Value;
+
+use crate::{Explanation, Project, Removal, manifest::Requirement, name::NormalizedName};
+
+/// At most this many near misses are shown for each dependency.
+const MAX_NEAR_MISSES: usize = 5;
+
+/// What to do with an unused dependency.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Choice {
+    /// Remove it from pyproject.toml.
+    Remove,
+    /// Add it to .poetryudepsignore, with an optional reason.
+    Ignore(Option<String>),
+    /// Leave it be.
+    Skip,
+}
+
+/// The outcome of reviewing the unused dependencies.
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct Review {
+    pub removed: Vec<Removal>,
+    /// The dependencies added to .poetryudepsignore.
+    pub ignored: Vec<String>,
+    /// The dependencies left as they are, which are still unused.
+    pub skipped: Vec<String>,
+}
+
+/// An unused dependency, and the evidence shown to the user.
+pub(crate) struct Finding {
+    pub explanation: Explanation,
+    /// The groups the dependency is declared in.
+    pub groups: Vec<String>,
+    /// The extras the dependency is part of.
+    pub extras: Vec<String>,
+}
+
+impl Finding {
+    pub fn new(
+        project: &Project,
+        explanation: Explanation,
+        extras: &BTreeMap<NormalizedName, BTreeSet<String>>,
+    ) -> Self {
+        let name = NormalizedName::new(&explanation.package);
+        let groups = project
+            .manifest
+            .groups
+            .iter()
+            .filter(|(_, reqs)| reqs.iter().any(|req| req.normalized == name))
+            .map(|(group, _)| group.clone())
+            .collect();
+        let extras = extras.get(&name).into_iter().flatten().cloned().collect();
+        Finding {
+            explanation,
+            groups,
+            extras,
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let explanation = &self.explanation;
+        writeln!(
+            f,
+            "{} (group: {})",
+            explanation.package,
+            self.groups.join(", ")
+        )?;
+        if !self.extras.is_empty() {
+            writeln!(f, "  Part of extras: {}", self.extras.join(", "))?;
+        }
+        let aliases: Vec<String> = explanation
+            .aliases
+            .iter()
+            .map(|(alias, source)| format!("{alias} ({source})"))
+            .collect();
+        writeln!(f, "  Module names tried: {}", aliases.join(", "))?;
+        if let Some(entry) = &explanation.name_map_entry {
+            writeln!(f, "  Name map entry: {entry}")?;
+        }
+        if !explanation.imported_by.is_empty() {
+            writeln!(f, "  Imported by other dependencies:")?;
+            for evidence in &explanation.imported_by {
+                writeln!(f, "    {evidence}")?;
+            }
+        }
+        if !explanation.near_misses.is_empty() {
+            writeln!(f, "  Near misses:")?;
+            for (evidence, reason) in explanation.near_misses.iter().take(MAX_NEAR_MISSES) {
+                writeln!(f, "    {evidence} ({reason})")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Ask the user what to do with each finding, returning one choice per finding.
+///
+/// Quitting early (or reaching the end of `input`) skips the remaining findings.
+pub(crate) fn prompt(
+    findings: &[Finding],
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> io::Result<Vec<Choice>> {
+    let mut choices = Vec::new();
+    'findings: for (i, finding) in findings.iter().enumerate() {
+        write!(output, "\n[{}/{}] {finding}", i + 1, findings.len())?;
+        loop {
+            let Some(answer) = ask(input, output, "Remove, ignore, skip or quit? [r/i/s/q] ")?
+            else {
+                break 'findings;
+            };
+            let choice = match answer.to_ascii_lowercase().as_str() {
+                "r" | "remove" => Choice::Remove,
+                "i" | "ignore" => {
+                    let reason = ask(input, output, "Reason (optional): ")?;
+                    Choice::Ignore(reason.filter(|reason| !reason.is_empty()))
+                }
+                "s" | "skip" | "" => Choice::Skip,
+                "q" | "quit" => break 'findings,
+                _ => {
+                    writeln!(output, "Please answer r, i, s or q.")?;
+                    continue;
+                }
+            };
+            choices.push(choice);
+            break;
+        }
+    }
+    choices.resize(findings.len(), Choice::Skip);
+    Ok(choices)
+}
+
+/// Ask a question, returning the trimmed answer, or `None` at the end of `input`.
+fn ask(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    question: &str,
+) -> io::Result<Option<String>> {
+    write!(output, "{question}")?;
+    output.flush()?;
+    let mut answer = String::new();
+    if input.read_line(&mut answer)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(answer.trim().to_string()))
+}
+
+/// Add packages to the ignore file, each preceded by its reason as a comment.
+pub(crate) fn append_ignores(path: &Path, ignores: &[(String, Option<String>)]) -> io::Result<()> {
+    let mut contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    for (package, reason) in ignores {
+        if let Some(reason) = reason {
+            contents.push_str("# ");
+            contents.push_str(reason);
+            contents.push('\n');
+        }
+        contents.push_str(package);
+        contents.push('\n');
+    }
+    fs::write(path, contents)
+}
+
+/// Read which extras each dependency is part of from pyproject.toml, if there is one.
+///
+/// Both Poetry's `tool.poetry.extras` and PEP 621 `project.optional-dependencies` are read.
+pub(crate) fn extras(path: &Path) -> BTreeMap<NormalizedName, BTreeSet<String>> {
+    let mut extras: BTreeMap<NormalizedName, BTreeSet<String>> = BTreeMap::new();
+    let Some(value) = fs::read_to_string(path)
+        .ok()
+        .and_then(|toml| toml.parse::<Value>().ok())
+    else {
+        return extras;
+    };
+    for table in ["tool.poetry.extras", "project.optional-dependencies"] {
+        let Some(table) = table
+            .split('.')
+            .try_fold(&value, |value, key| value.get(key))
+            .and_then(Value::as_table)
+        else {
+            continue;
+        };
+        for (extra, reqs) in table {
+            for req in reqs
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .filter_map(Requirement::parse)
+            {
+                extras
+                    .entry(req.normalized)
+                    .or_default()
+                    .insert(extra.clone());
+            }
+        }
+    }
+    extras
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::why::Evidence;
+    use pretty_assertions::assert_eq;
+
+    fn finding(package: &str) -> Finding {
+        Finding {
+            explanation: Explanation {
+                package: package.to_string(),
+                main: true,
+                finding: None,
+                ignored: false,
+                suppression: None,
+                aliases: vec![(package.to_string(), "distribution name".to_string())],
+                name_map_entry: None,
+                credited: Vec::new(),
+                imported_by: Vec::new(),
+                near_misses: Vec::new(),
+            },
+            groups: vec!["main".to_string()],
+            extras: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_prompt() {
+        let findings = ["asyncpg", "redis", "boto3", "numpy"].map(finding);
+        let mut input = "x\nr\ni\nused via entry points\nq\n".as_bytes();
+        let mut output = Vec::new();
+        let choices = prompt(&findings, &mut input, &mut output).unwrap();
+        assert_eq!(
+            choices,
+            [
+                Choice::Remove,
+                Choice::Ignore(Some("used via entry points".to_string())),
+                Choice::Skip,
+                Choice::Skip
+            ]
+        );
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("[1/4] asyncpg (group: main)"));
+        assert!(output.contains("Please answer r, i, s or q."));
+        assert!(output.contains("[3/4] boto3"));
+        assert!(!output.contains("[4/4]"));
+    }
+
+    #[test]
+    fn test_display() {
+        let mut finding = finding("asyncpg");
+        finding.extras = vec!["postgres".to_string()];
+        finding.explanation.imported_by.push(Evidence {
+            path: "sqlalchemy/dialects/postgresql/asyncpg.py".into(),
+            import: crate::parser::ImportStatement {
+                package: "asyncpg".to_string(),
+                module: String::new(),
+                line: 12,
+            },
+            owner: Some(NormalizedName::new("sqlalchemy")),
+        });
+        assert_eq!(
+            finding.to_string(),
+            "asyncpg (group: main)
+  Part of extras: postgres
+  Module names tried: asyncpg (distribution name)
+  Imported by other dependencies:
+    sqlalchemy/dialects/postgresql/asyncpg.py:12: import asyncpg (in sqlalchemy)
+"
+        );
+    }
+
+    #[test]
+    fn test_append_ignores() {
+        let root = tempfile::tempdir().unwrap();
+        let path = root.path().join(".poetryudepsignore");
+        fs::write(&path, "# Plugins\nasyncpg").unwrap();
+        append_ignores(
+            &path,
+            &[
+                ("redis".to_string(), Some("cache backend".to_string())),
+                ("boto3".to_string(), None),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "# Plugins\nasyncpg\n# cache backend\nredis\nboto3\n"
+        );
+    }
+
+    #[test]
+    fn test_extras() {
+        let root = tempfile::tempdir().unwrap();
+        let path = root.path().join("pyproject.toml");
+        fs::write(
+            &path,
+            r#"
+[project.optional-dependencies]
+postgres = ["asyncpg>=0.29", "SQLAlchemy[asyncio]"]
+
+[tool.poetry.extras]
+cache = ["redis"]
+pg = ["asyncpg"]
+"#,
+        )
+        .unwrap();
+        let extras = extras(&path);
+        assert_eq!(
+            extras["asyncpg"],
+            BTreeSet::from(["pg".to_string(), "postgres".to_string()])
+        );
+        assert_eq!(extras["redis"], BTreeSet::from(["cache".to_string()]));
+        assert!(!extras.contains_key("numpy"));
+    }
+}
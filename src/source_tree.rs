@@ -0,0 +1,253 @@
+//! Inspecting the source tree of a project, such as a local (path) dependency.
+//!
+//! A path dependency is not necessarily installed anywhere we can look, and its module names
+//! often differ from its distribution name, so we read its own pyproject.toml and package layout
+//! instead. The packages a project configures also tell which of its own files are package code.
+
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use toml::Value;
+use tracing::debug;
+
+use crate::manifest::{PYPROJECT, Pyproject};
+
+/// Files and directories which sit next to packages, but which are never imported by dependents.
+const NOT_MODULES: &[&str] = &[
+    "conftest", "docs", "examples", "fabfile", "manage", "noxfile", "setup", "test", "tests",
+];
+
+/// A package or module configured for the build tool.
+struct Package {
+    /// Where it is, relative to the project root, e.g. `src/my_package` or `extra.py`.
+    path: PathBuf,
+    /// Its dotted module path, e.g. `my_package`.
+    module: String,
+}
+
+/// Read the distribution name of the project at `root`.
+pub fn project_name(root: &Path) -> Option<String> {
+    let pyproject = read_pyproject(root, None).ok()?;
+    ["project", "tool.poetry"].iter().find_map(|table| {
+        pyproject
+            .get(&format!("{table}.name"))
+            .and_then(Value::as_str)
+            .map(ToString::to_string)
+    })
+}
+
+/// Find the top-level modules provided by the project at `root`.
+///
+/// Packages explicitly configured for Poetry, setuptools or Hatch are used when present.
+/// Otherwise, the `src/` layout or the project root is searched for packages and modules.
+pub fn provided_modules(root: &Path) -> Result<BTreeSet<String>> {
+    let packages = read_pyproject(root, None)
+        .map(|pyproject| configured_packages(&pyproject))
+        .unwrap_or_default();
+    let mut modules: BTreeSet<String> = packages
+        .into_iter()
+        .map(|package| package.module)
+        // Globs are not modules of their own
+        .filter(|module| !module.contains('*') && !module.is_empty())
+        .collect();
+
+    if modules.is_empty() {
+        let src = root.join("src");
+        let layout = if src.is_dir() {
+            src
+        } else {
+            root.to_path_buf()
+        };
+        modules = discover_modules(&layout)?;
+    }
+    debug!(root = %root.display(), ?modules, "Read path dependency");
+    Ok(modules)
+}
+
+/// Read the pyproject.toml of the project at `root`, or its `unsaved` contents.
+fn read_pyproject(root: &Path, unsaved: Option<&str>) -> Result<Pyproject> {
+    let path = root.join(PYPROJECT);
+    let toml = match unsaved {
+        Some(toml) => toml.to_string(),
+        None => fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?,
+    };
+    Pyproject::parse(root.to_path_buf(), &toml)
+        .with_context(|| format!("failed to parse {}", path.display()))
+}
+
+/// The packages and modules listed in the build configuration.
+fn configured_packages(pyproject: &Pyproject) -> Vec<Package> {
+    let strings = |key| {
+        pyproject
+            .get(key)
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+    };
+    let mut packages = Vec::new();
+
+    // Poetry: `packages = [{ include = "my_package", from = "src" }]`
+    for package in pyproject
+        .get("tool.poetry.packages")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        if let Some(include) = package.get("include").and_then(Value::as_str) {
+            let from = package.get("from").and_then(Value::as_str).unwrap_or(".");
+            packages.push(Package {
+                path: Path::new(from).join(include),
+                module: path_to_module(include),
+            });
+        }
+    }
+
+    // setuptools: packages and modules are dotted paths, under `package-dir` if set
+    let package_dir = pyproject
+        .get("tool.setuptools.package-dir")
+        .and_then(|dirs| dirs.get(""))
+        .and_then(Value::as_str)
+        .unwrap_or(".");
+    for (key, suffix) in [
+        ("tool.setuptools.packages", ""),
+        ("tool.setuptools.py-modules", ".py"),
+    ] {
+        for module in strings(key) {
+            packages.push(Package {
+                path: Path::new(package_dir).join(module.replace('.', "/") + suffix),
+                module: module.to_string(),
+            });
+        }
+    }
+
+    // Hatch: `packages = ["src/my_package"]`, relative to the project root
+    for package in strings("tool.hatch.build.targets.wheel.packages") {
+        let path = PathBuf::from(package);
+        let module = path
+            .file_name()
+            .map_or(package.into(), |name| name.to_string_lossy());
+        packages.push(Package {
+            module: module.into_owned(),
+            path,
+        });
+    }
+
+    packages
+}
+
+/// Globs matching the package code of the project at `root`, as configured for Poetry,
+/// setuptools or Hatch in pyproject.toml or its `unsaved` contents, or nothing if no packages
+/// are configured.
+///
+/// The globs are anchored to `root`, e.g. `/src/my_package`.
+pub fn package_globs(root: &Path, unsaved: Option<&str>) -> Vec<String> {
+    let Ok(pyproject) = read_pyproject(root, unsaved) else {
+        return Vec::new();
+    };
+    configured_packages(&pyproject)
+        .iter()
+        .map(|package| {
+            let glob = package.path.strip_prefix(".").unwrap_or(&package.path);
+            format!("/{}", glob.to_string_lossy().trim_end_matches('/'))
+        })
+        .collect()
+}
+
+/// Turn an include path like `my_package/` or `my_module.py` into a module path.
+fn path_to_module(include: &str) -> String {
+    let include = include.trim_end_matches('/');
+    let include = include.strip_suffix(".py").unwrap_or(include);
+    include.replace('/', ".")
+}
+
+/// Find the packages (directories with an `__init__.py`) and modules in `dir`.
+fn discover_modules(dir: &Path) -> Result<BTreeSet<String>> {
+    let mut modules = BTreeSet::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))? {
+        let path: PathBuf = entry?.path();
+        let Some(name) = path.file_stem().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let is_package = path.is_dir() && path.join("__init__.py").is_file();
+        let is_module = path.extension().is_some_and(|ext| ext == "py");
+        if (is_package || is_module) && !NOT_MODULES.contains(&name) {
+            modules.insert(name.to_string());
+        }
+    }
+    Ok(modules)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_poetry_packages() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(
+            root.path().join("pyproject.toml"),
+            r#"
+[tool.poetry]
+name = "acme-widgets"
+packages = [{ include = "widgets", from = "src" }, { include = "extra.py" }]
+"#,
+        )
+        .unwrap();
+        assert_eq!(project_name(root.path()).as_deref(), Some("acme-widgets"));
+        assert_eq!(
+            package_globs(root.path(), None),
+            ["/src/widgets", "/extra.py"]
+        );
+        assert_eq!(
+            provided_modules(root.path()).unwrap(),
+            BTreeSet::from(["extra".to_string(), "widgets".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_setuptools_package_globs() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(
+            root.path().join("pyproject.toml"),
+            r#"
+[tool.setuptools]
+packages = ["widgets", "widgets.cli"]
+py-modules = ["extra"]
+package-dir = { "" = "src" }
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            package_globs(root.path(), None),
+            ["/src/widgets", "/src/widgets/cli", "/src/extra.py"]
+        );
+    }
+
+    #[test]
+    fn test_src_layout() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(
+            root.path().join("pyproject.toml"),
+            "[project]\nname = \"internal-lib\"\n",
+        )
+        .unwrap();
+        let src = root.path().join("src");
+        fs::create_dir_all(src.join("corp_internal")).unwrap();
+        fs::write(src.join("corp_internal/__init__.py"), "").unwrap();
+        fs::create_dir_all(src.join("not_a_package")).unwrap();
+        fs::write(src.join("helpers.py"), "").unwrap();
+        fs::write(src.join("conftest.py"), "").unwrap();
+        assert_eq!(
+            provided_modules(root.path()).unwrap(),
+            BTreeSet::from(["corp_internal".to_string(), "helpers".to_string()])
+        );
+        assert!(package_globs(root.path(), None).is_empty());
+    }
+}
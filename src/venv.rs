@@ -0,0 +1,254 @@
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::Result;
+use tracing::{debug, warn};
+
+use crate::name::NormalizedName;
+
+/// An installed distribution found in a virtualenv's site-packages.
+#[derive(Debug)]
+pub struct Distribution {
+    /// The name of the dependency which installed the distribution.
+    pub name: NormalizedName,
+    /// The Python source files owned by the distribution, according to its `RECORD`.
+    pub files: Vec<PathBuf>,
+    /// The modules provided by the distribution, e.g. `google.cloud.storage`, one per Python
+    /// file. Their parent packages are not included, unless the distribution has files of theirs.
+    pub modules: BTreeSet<String>,
+}
+
+/// Find the site-packages directories of a virtualenv.
+///
+/// This covers both the POSIX layout (`lib/python3.X/site-packages`, and the `lib64` variant)
+/// and the Windows layout (`Lib/site-packages`).
+pub fn site_packages(venv: &Path) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    let windows = venv.join("Lib").join("site-packages");
+    if windows.is_dir() {
+        dirs.push(windows);
+    }
+    for lib in ["lib", "lib64"] {
+        let Ok(entries) = fs::read_dir(venv.join(lib)) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let candidate = entry.path().join("site-packages");
+            if entry.file_name().to_string_lossy().starts_with("python")
+                && candidate.is_dir()
+                // lib64 is frequently a symlink to lib
+                && !dirs.iter().any(|d| same_file(d, &candidate))
+            {
+                dirs.push(candidate);
+            }
+        }
+    }
+    dirs
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Read the installed distributions of the `wanted` dependencies.
+pub fn find_distributions(
+    venv: &Path,
+    wanted: &BTreeSet<NormalizedName>,
+) -> Result<Vec<Distribution>> {
+    let mut dists = Vec::new();
+    for site in site_packages(venv) {
+        debug!(site = %site.display(), "Reading distributions");
+        for entry in fs::read_dir(&site)? {
+            let entry = entry?;
+            let dir_name = entry.file_name().to_string_lossy().into_owned();
+            let Some(stem) = dir_name.strip_suffix(".dist-info") else {
+                continue;
+            };
+            let dist_info = entry.path();
+            let name = metadata_name(&dist_info)
+                .unwrap_or_else(|| stem.split('-').next().unwrap_or(stem).to_string());
+            let name = NormalizedName::new(&name);
+            if !wanted.contains(&name) {
+                continue;
+            }
+            let files = match fs::read_to_string(dist_info.join("RECORD")) {
+                Ok(record) => record_python_files(&record),
+                Err(e) => {
+                    warn!(package = %name, error = %e, "Unable to read RECORD");
+                    continue;
+                }
+            };
+            debug!(package = %name, files = files.len(), "Found distribution");
+            let modules = files.iter().filter_map(|f| module_path(f)).collect();
+            let files = files.iter().map(|f| site.join(f)).collect();
+            dists.push(Distribution {
+                name,
+                files,
+                modules,
+            });
+        }
+    }
+    Ok(dists)
+}
+
+/// Read the `Name` field from a distribution's core metadata.
+fn metadata_name(dist_info: &Path) -> Option<String> {
+    let metadata = fs::read_to_string(dist_info.join("METADATA")).ok()?;
+    metadata
+        .lines()
+        // Headers end at the first blank line
+        .take_while(|line| !line.is_empty())
+        .find_map(|line| line.strip_prefix("Name:"))
+        .map(|name| name.trim().to_string())
+}
+
+/// Parse the path column of a `RECORD` file, keeping only Python sources.
+///
+/// `RECORD` is a CSV file, so paths containing commas are quoted. Paths are relative to
+/// site-packages.
+fn record_python_files(record: &str) -> Vec<PathBuf> {
+    record
+        .lines()
+        .filter_map(|line| {
+            let path = if let Some(quoted) = line.strip_prefix('"') {
+                let mut path = String::new();
+                let mut chars = quoted.chars().peekable();
+                while let Some(c) = chars.next() {
+                    if c == '"' {
+                        if chars.peek() == Some(&'"') {
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                    path.push(c);
+                }
+                path
+            } else {
+                line.split(',').next()?.to_string()
+            };
+            Path::new(&path)
+                .extension()
+                .is_some_and(|ext| ext == "py")
+                .then(|| PathBuf::from(path))
+        })
+        .collect()
+}
+
+/// The dotted module path of a source file relative to site-packages.
+///
+/// For example, `google/cloud/storage/blob.py` gives `google.cloud.storage.blob`, and
+/// `google/cloud/storage/__init__.py` gives `google.cloud.storage`. Parents are not modules of
+/// their own, since a namespace package like `google` has no `__init__.py` and is shared by many
+/// distributions. Files outside of site-packages, such as scripts, do not provide any modules.
+fn module_path(file: &Path) -> Option<String> {
+    let mut parts = Vec::new();
+    for component in file.with_extension("").components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy().into_owned()),
+            _ => return None,
+        }
+    }
+    if parts.last().is_some_and(|last| last == "__init__") {
+        parts.pop();
+    }
+    Some(parts.join(".")).filter(|path| !path.is_empty())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_record_python_files() {
+        let record = r#"yaml/__init__.py,sha256=abc,1234
+yaml/_yaml.cpython-312-x86_64-linux-gnu.so,sha256=def,5678
+"yaml/odd,name.py",sha256=ghi,9
+PyYAML-6.0.1.dist-info/RECORD,,
+../../../bin/yaml-tool,sha256=jkl,10
+"#;
+        assert_eq!(
+            record_python_files(record),
+            [
+                PathBuf::from("yaml/__init__.py"),
+                PathBuf::from("yaml/odd,name.py")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_module_path() {
+        assert_eq!(
+            module_path(Path::new("google/cloud/storage/blob.py")).as_deref(),
+            Some("google.cloud.storage.blob")
+        );
+        assert_eq!(
+            module_path(Path::new("yaml/__init__.py")).as_deref(),
+            Some("yaml")
+        );
+        assert_eq!(module_path(Path::new("../../bin/tool.py")), None);
+    }
+
+    #[test]
+    fn test_find_distributions() {
+        let venv = tempfile::tempdir().unwrap();
+        let site = venv.path().join("lib/python3.12/site-packages");
+        let dist_info = site.join("PyYAML-6.0.1.dist-info");
+        fs::create_dir_all(&dist_info).unwrap();
+        fs::create_dir_all(site.join("pip-24.0.dist-info")).unwrap();
+        fs::write(
+            dist_info.join("METADATA"),
+            "Metadata-Version: 2.1\nName: PyYAML\nVersion: 6.0.1\n\nName: not-a-header\n",
+        )
+        .unwrap();
+        fs::write(
+            dist_info.join("RECORD"),
+            "yaml/__init__.py,,\nyaml/loader.py,,\n",
+        )
+        .unwrap();
+
+        let wanted = BTreeSet::from([NormalizedName::new("pyyaml")]);
+        let dists = find_distributions(venv.path(), &wanted).unwrap();
+        assert_eq!(dists.len(), 1);
+        assert_eq!(dists[0].name.as_str(), "pyyaml");
+        assert_eq!(
+            dists[0].files,
+            [site.join("yaml/__init__.py"), site.join("yaml/loader.py")]
+        );
+        assert_eq!(
+            dists[0].modules,
+            BTreeSet::from(["yaml".to_string(), "yaml.loader".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_find_distributions_namespace() {
+        let venv = tempfile::tempdir().unwrap();
+        let site = venv.path().join("lib/python3.12/site-packages");
+        let dist_info = site.join("google_cloud_storage-2.16.0.dist-info");
+        fs::create_dir_all(&dist_info).unwrap();
+        fs::write(
+            dist_info.join("RECORD"),
+            "google/cloud/storage/__init__.py,,\ngoogle/cloud/storage/blob.py,,\n",
+        )
+        .unwrap();
+
+        let wanted = BTreeSet::from([NormalizedName::new("google-cloud-storage")]);
+        let dists = find_distributions(venv.path(), &wanted).unwrap();
+        // The namespace packages `google` and `google.cloud` belong to no distribution
+        assert_eq!(
+            dists[0].modules,
+            BTreeSet::from([
+                "google.cloud.storage".to_string(),
+                "google.cloud.storage.blob".to_string()
+            ])
+        );
+    }
+}
@@ -0,0 +1,441 @@
+//! Watching a project for changes, to check it again and report what changed.
+//!
+//! On Linux, the project's directories are watched with inotify. Elsewhere, or if inotify is not
+//! available (e.g. because the limit of watches is reached, even after watching started), the
+//! project is polled instead.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use ignore::WalkBuilder;
+use tracing::{debug, info};
+
+use crate::manifest::ManifestKind;
+use crate::report::{Finding, Report};
+
+/// How long to wait for more changes after one, so that saving many files checks once.
+const DEBOUNCE: Duration = Duration::from_millis(100);
+/// How often the project is polled without inotify.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The files which are neither Python source nor manifests, but whose changes can change the
+/// findings.
+const PROJECT_FILES: &[&str] = &["poetry.lock", ".poetryudepsignore"];
+
+/// Whether a change to the file at `path` can change the findings.
+fn is_relevant(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "py")
+        || ManifestKind::from_path(path).is_some()
+        || path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| PROJECT_FILES.contains(&name))
+}
+
+/// Waits for changes to the relevant files of a project.
+pub enum Watcher {
+    #[cfg(target_os = "linux")]
+    Inotify(inotify::Watcher),
+    /// The size and modification time of every relevant file.
+    Poll {
+        root: PathBuf,
+        files: BTreeMap<PathBuf, (u64, Option<SystemTime>)>,
+    },
+}
+
+impl Watcher {
+    /// Watch the project in `root`, skipping the directories which are ignored or hidden.
+    pub fn new(root: &Path) -> Watcher {
+        #[cfg(target_os = "linux")]
+        match inotify::Watcher::new(root) {
+            Ok(watcher) => return Watcher::Inotify(watcher),
+            Err(e) => info!("Polling for changes, as inotify is not available: {e:#}"),
+        }
+        Watcher::Poll {
+            root: root.to_path_buf(),
+            files: snapshot(root),
+        }
+    }
+
+    /// Block until relevant files change, returning them.
+    pub fn wait(&mut self) -> BTreeSet<PathBuf> {
+        loop {
+            match self {
+                #[cfg(target_os = "linux")]
+                Watcher::Inotify(watcher) => {
+                    if let Err(e) = watcher.wait() {
+                        return self.poll_instead(&e);
+                    }
+                }
+                Watcher::Poll { .. } => thread::sleep(POLL_INTERVAL),
+            }
+            let changed = self.changes();
+            if !changed.is_empty() {
+                // Let a burst of changes settle
+                thread::sleep(DEBOUNCE);
+                let mut changed = changed;
+                changed.extend(self.changes());
+                return changed;
+            }
+        }
+    }
+
+    /// The relevant files which changed since the last call, without blocking.
+    ///
+    /// If inotify fails, e.g. because a new directory exceeds the limit of watches, the project is
+    /// polled from then on.
+    pub fn changes(&mut self) -> BTreeSet<PathBuf> {
+        match self {
+            #[cfg(target_os = "linux")]
+            Watcher::Inotify(watcher) => match watcher.changes() {
+                Ok(changed) => changed,
+                Err(e) => self.poll_instead(&e),
+            },
+            Watcher::Poll { root, files } => {
+                let current = snapshot(root);
+                let changed = current
+                    .iter()
+                    .filter(|(path, stat)| files.get(*path) != Some(stat))
+                    .map(|(path, _)| path)
+                    .chain(files.keys().filter(|path| !current.contains_key(*path)))
+                    .cloned()
+                    .collect();
+                *files = current;
+                changed
+            }
+        }
+    }
+
+    /// Poll the project from now on, as inotify failed with `error`.
+    ///
+    /// Changes may have been missed, so every relevant file is taken to have changed.
+    #[cfg(target_os = "linux")]
+    fn poll_instead(&mut self, error: &anyhow::Error) -> BTreeSet<PathBuf> {
+        info!("Polling for changes, as inotify failed: {error:#}");
+        let root = match self {
+            Watcher::Inotify(watcher) => watcher.root().to_path_buf(),
+            Watcher::Poll { root, .. } => root.clone(),
+        };
+        let files = snapshot(&root);
+        let changed = files.keys().cloned().collect();
+        *self = Watcher::Poll { root, files };
+        changed
+    }
+}
+
+/// The entries under `dir` in the project in `root`, including `dir` itself, which are neither
+/// ignored nor hidden.
+///
+/// The project is walked from its root, so that the .gitignore files of the directories above
+/// `dir` apply too, but only down the path to `dir`.
+fn walk(root: &Path, dir: &Path) -> impl Iterator<Item = ignore::DirEntry> {
+    let dir = dir.to_path_buf();
+    WalkBuilder::new(root)
+        .standard_filters(true)
+        .filter_entry({
+            let dir = dir.clone();
+            move |entry| dir.starts_with(entry.path()) || entry.path().starts_with(&dir)
+        })
+        .build()
+        .filter_map(Result::ok)
+        .filter(move |entry| entry.path().starts_with(&dir))
+}
+
+/// The directories under `dir` in the project in `root` which are worth watching.
+fn directories(root: &Path, dir: &Path) -> impl Iterator<Item = PathBuf> {
+    walk(root, dir)
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_dir()))
+        .map(ignore::DirEntry::into_path)
+}
+
+/// The size and modification time of every relevant file in the project in `root`.
+///
+/// The walk skips hidden files, so the project files in the root, like .poetryudepsignore, are
+/// added explicitly.
+fn snapshot(root: &Path) -> BTreeMap<PathBuf, (u64, Option<SystemTime>)> {
+    WalkBuilder::new(root)
+        .standard_filters(true)
+        .build()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()) && is_relevant(entry.path()))
+        .map(ignore::DirEntry::into_path)
+        .chain(PROJECT_FILES.iter().map(|name| root.join(name)))
+        .filter_map(|path| {
+            let metadata = fs::metadata(&path).ok().filter(fs::Metadata::is_file)?;
+            Some((path, (metadata.len(), metadata.modified().ok())))
+        })
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+mod inotify {
+    use std::{
+        collections::{BTreeSet, HashMap},
+        mem::MaybeUninit,
+        os::unix::ffi::OsStrExt,
+        path::{Path, PathBuf},
+    };
+
+    use anyhow::{Context, Result};
+    use rustix::{
+        event::{PollFd, PollFlags, poll},
+        fd::OwnedFd,
+        fs::inotify::{self, CreateFlags, ReadFlags, Reader, WatchFlags},
+        io::Errno,
+    };
+    use tracing::debug;
+
+    use super::{directories, is_relevant, walk};
+
+    /// Watches every directory of a project with inotify.
+    pub struct Watcher {
+        root: PathBuf,
+        fd: OwnedFd,
+        /// Watch descriptor -> the directory watched.
+        directories: HashMap<i32, PathBuf>,
+    }
+
+    impl Watcher {
+        pub fn new(root: &Path) -> Result<Watcher> {
+            let fd = inotify::init(CreateFlags::NONBLOCK | CreateFlags::CLOEXEC)
+                .context("failed to initialize inotify")?;
+            let mut watcher = Watcher {
+                root: root.to_path_buf(),
+                fd,
+                directories: HashMap::new(),
+            };
+            watcher.add(root)?;
+            Ok(watcher)
+        }
+
+        pub fn root(&self) -> &Path {
+            &self.root
+        }
+
+        /// Block until there are events to read.
+        pub fn wait(&self) -> Result<()> {
+            let mut fds = [PollFd::new(&self.fd, PollFlags::IN)];
+            loop {
+                match poll(&mut fds, None) {
+                    Ok(_) => return Ok(()),
+                    Err(Errno::INTR) => {}
+                    Err(e) => return Err(e).context("failed to wait for inotify events"),
+                }
+            }
+        }
+
+        /// Watch every directory under `dir`, including itself.
+        fn add(&mut self, dir: &Path) -> Result<()> {
+            let flags = WatchFlags::CLOSE_WRITE
+                | WatchFlags::MODIFY
+                | WatchFlags::CREATE
+                | WatchFlags::DELETE
+                | WatchFlags::MOVED_FROM
+                | WatchFlags::MOVED_TO;
+            for dir in directories(&self.root, dir) {
+                let wd = inotify::add_watch(&self.fd, &dir, flags)
+                    .with_context(|| format!("failed to watch {}", dir.display()))?;
+                self.directories.insert(wd, dir);
+            }
+            Ok(())
+        }
+
+        pub fn changes(&mut self) -> Result<BTreeSet<PathBuf>> {
+            let mut changed = BTreeSet::new();
+            let mut created = Vec::new();
+            let mut buf = [MaybeUninit::uninit(); 4096];
+            let mut reader = Reader::new(&self.fd, &mut buf);
+            loop {
+                let event = match reader.next() {
+                    Ok(event) => event,
+                    Err(Errno::WOULDBLOCK) => break,
+                    Err(e) => return Err(e).context("failed to read inotify events"),
+                };
+                if event.events().contains(ReadFlags::IGNORED) {
+                    // The directory was removed
+                    self.directories.remove(&event.wd());
+                    continue;
+                }
+                let (Some(dir), Some(name)) =
+                    (self.directories.get(&event.wd()), event.file_name())
+                else {
+                    continue;
+                };
+                let path = dir.join(std::ffi::OsStr::from_bytes(name.to_bytes()));
+                if event.events().contains(ReadFlags::ISDIR) {
+                    if event
+                        .events()
+                        .intersects(ReadFlags::CREATE | ReadFlags::MOVED_TO)
+                    {
+                        created.push(path);
+                    }
+                } else if is_relevant(&path) {
+                    changed.insert(path);
+                }
+            }
+            for dir in created {
+                debug!(dir = %dir.display(), "Watching new directory");
+                self.add(&dir)?;
+                // Files may have been written before the directory was watched
+                changed.extend(
+                    walk(&self.root, &dir)
+                        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+                        .map(ignore::DirEntry::into_path)
+                        .filter(|path| is_relevant(path)),
+                );
+            }
+            Ok(changed)
+        }
+    }
+}
+
+/// What identifies a finding across runs.
+fn key(finding: &Finding) -> (Option<&Path>, &str, &str, Option<&str>) {
+    (
+        finding.project.as_deref(),
+        finding.kind.rule_id(),
+        &finding.normalized,
+        finding.group.as_deref(),
+    )
+}
+
+/// Describe how the findings changed from `old` to `new`, e.g. `requests: now unused`.
+pub fn diff(old: &Report, new: &Report) -> Vec<String> {
+    let old_keys: BTreeSet<_> = old.findings.iter().map(key).collect();
+    let new_keys: BTreeSet<_> = new.findings.iter().map(key).collect();
+    let describe = |finding: &Finding, change: &str| {
+        let package = match &finding.project {
+            Some(project) => format!("{} ({})", finding.package, project.display()),
+            None => finding.package.clone(),
+        };
+        format!("{package}: {change} {}", finding.kind.rule_id())
+    };
+    let resolved = old
+        .findings
+        .iter()
+        .filter(|finding| !new_keys.contains(&key(finding)))
+        .map(|finding| describe(finding, "no longer"));
+    let found = new
+        .findings
+        .iter()
+        .filter(|finding| !old_keys.contains(&key(finding)))
+        .map(|finding| describe(finding, "now"));
+    let lines = resolved.chain(found).collect();
+    debug!(?lines, "Findings changed");
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::report::FindingKind;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+
+    fn finding(package: &str, kind: FindingKind) -> Finding {
+        Finding {
+            kind,
+            package: package.to_string(),
+            normalized: package.to_lowercase(),
+            group: Some("main".to_string()),
+            location: None,
+            aliases: Vec::new(),
+            required_by: Vec::new(),
+            project: None,
+            suppression: None,
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = Report {
+            findings: vec![
+                finding("PyYAML", FindingKind::Unused),
+                finding("pytest", FindingKind::Misplaced),
+            ],
+            ..Report::default()
+        };
+        let new = Report {
+            findings: vec![
+                finding("pytest", FindingKind::Misplaced),
+                finding("requests", FindingKind::Unused),
+            ],
+            ..Report::default()
+        };
+        assert_eq!(
+            diff(&old, &new),
+            ["PyYAML: no longer unused", "requests: now unused"]
+        );
+        assert!(diff(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn test_is_relevant() {
+        assert!(is_relevant(Path::new("src/app.py")));
+        assert!(is_relevant(Path::new("pyproject.toml")));
+        assert!(is_relevant(Path::new("requirements-dev.txt")));
+        assert!(!is_relevant(Path::new("src/.app.py.swp")));
+        assert!(!is_relevant(Path::new("README.md")));
+    }
+
+    #[test]
+    fn test_watcher() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(root.path().join("app.py"), "import os\n").unwrap();
+        fs::write(root.path().join(".ignore"), "build/\n").unwrap();
+        let mut watchers = vec![Watcher::new(root.path())];
+        watchers.push(Watcher::Poll {
+            root: root.path().to_path_buf(),
+            files: snapshot(root.path()),
+        });
+
+        fs::write(root.path().join("app.py"), "import requests\n").unwrap();
+        fs::write(root.path().join("notes.md"), "").unwrap();
+        fs::create_dir(root.path().join("pkg")).unwrap();
+        fs::write(root.path().join("pkg/mod.py"), "").unwrap();
+        // New directories which are ignored or hidden are not watched
+        for dir in ["build", ".cache"] {
+            fs::create_dir(root.path().join(dir)).unwrap();
+            fs::write(root.path().join(dir).join("gen.py"), "").unwrap();
+        }
+        for watcher in &mut watchers {
+            let changed: Vec<PathBuf> = watcher
+                .changes()
+                .iter()
+                .map(|path| path.strip_prefix(root.path()).unwrap().to_path_buf())
+                .collect();
+            assert_eq!(changed, [Path::new("app.py"), Path::new("pkg/mod.py")]);
+        }
+    }
+
+    #[test]
+    fn test_poll_ignore_file() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(root.path().join("app.py"), "import os\n").unwrap();
+        let mut watcher = Watcher::Poll {
+            root: root.path().to_path_buf(),
+            files: snapshot(root.path()),
+        };
+
+        fs::write(root.path().join(".poetryudepsignore"), "requests\n").unwrap();
+        assert_eq!(
+            watcher.changes(),
+            BTreeSet::from([root.path().join(".poetryudepsignore")])
+        );
+        fs::write(root.path().join(".poetryudepsignore"), "requests\nPyYAML\n").unwrap();
+        assert_eq!(
+            watcher.changes(),
+            BTreeSet::from([root.path().join(".poetryudepsignore")])
+        );
+        fs::remove_file(root.path().join(".poetryudepsignore")).unwrap();
+        assert_eq!(
+            watcher.changes(),
+            BTreeSet::from([root.path().join(".poetryudepsignore")])
+        );
+    }
+}
@@ -0,0 +1,429 @@
+//! Explaining why a dependency is considered used or unused.
+
+use std::{collections::BTreeSet, fmt, path::PathBuf};
+
+use anyhow::{Result, bail};
+
+use crate::{
+    Import, Project, credited_by, import_aliases,
+    manifest::Suppression,
+    name::NormalizedName,
+    name_map::known_entry,
+    parser::ImportStatement,
+    report::{Finding, FindingKind, Report},
+};
+
+/// At most this many near misses are shown.
+const MAX_NEAR_MISSES: usize = 10;
+
+/// An import, and where it was found.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Evidence {
+    pub path: PathBuf,
+    pub import: ImportStatement,
+    /// The dependency whose code contains the import, or `None` for project code.
+    pub owner: Option<NormalizedName>,
+}
+
+impl fmt::Display for Evidence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}",
+            self.path.display(),
+            self.import.line,
+            self.import
+        )?;
+        if let Some(owner) = &self.owner {
+            write!(f, " (in {owner})")?;
+        }
+        Ok(())
+    }
+}
+
+/// Why a dependency is considered used or unused.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Explanation {
+    /// The dependency, as spelled in the manifest.
+    pub package: String,
+    /// Whether this is a main dependency, rather than a dev one.
+    pub main: bool,
+    /// The problem found with the dependency when checking the project, whether or not it is
+    /// ignored, or `None` if it is used where it is declared.
+    pub finding: Option<Finding>,
+    /// Whether the dependency is listed in .poetryudepsignore.
+    pub ignored: bool,
+    /// The comment ignoring the dependency where it is declared, if any.
+    pub suppression: Option<Suppression>,
+    /// The module names the dependency may be imported by, and where each was learned from.
+    pub aliases: Vec<(String, String)>,
+    /// The name map entry used for the dependency, if any.
+    pub name_map_entry: Option<String>,
+    /// The imports in the project which credit the dependency.
+    pub credited: Vec<Evidence>,
+    /// The imports in other used dependencies which credit the dependency.
+    pub imported_by: Vec<Evidence>,
+    /// Imports in the project which nearly credited the dependency, and why they did not.
+    pub near_misses: Vec<(Evidence, String)>,
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = if self.main { "main" } else { "dev" };
+        writeln!(f, "{} is a {kind} dependency.", self.package)?;
+        match &self.finding {
+            None if self.credited.is_empty() => {
+                writeln!(f, "It is used: other used dependencies import it.")?;
+            }
+            None => writeln!(
+                f,
+                "It is used: {} import(s) in the project credit it.",
+                self.credited.len()
+            )?,
+            Some(finding) if finding.kind == FindingKind::Unused => {
+                writeln!(f, "It is unused: no import in the project credits it.")?;
+            }
+            Some(finding) => writeln!(
+                f,
+                "It is {}: {}.",
+                finding.kind.rule_id(),
+                finding.message()
+            )?,
+        }
+        if self.ignored {
+            writeln!(
+                f,
+                "It is listed in .poetryudepsignore, so it is never reported."
+            )?;
+        }
+        match self.suppression.as_ref().map(|s| s.reason.as_deref()) {
+            Some(Some(reason)) => writeln!(
+                f,
+                "It is ignored by a comment where it is declared, so it is never reported: {reason}"
+            )?,
+            Some(None) => writeln!(
+                f,
+                "It is ignored by a comment where it is declared, so it is never reported."
+            )?,
+            None => (),
+        }
+
+        writeln!(f, "\nModule names:")?;
+        for (alias, source) in &self.aliases {
+            writeln!(f, "  {alias} ({source})")?;
+        }
+        if let Some(entry) = &self.name_map_entry {
+            writeln!(f, "Name map entry: {entry}")?;
+        }
+
+        if !self.credited.is_empty() {
+            writeln!(f, "\nCredited by:")?;
+            for evidence in &self.credited {
+                writeln!(f, "  {evidence}")?;
+            }
+        }
+        if !self.imported_by.is_empty() {
+            writeln!(f, "\nImported by other used dependencies:")?;
+            for evidence in &self.imported_by {
+                writeln!(f, "  {evidence}")?;
+            }
+        }
+        if self.credited.is_empty() && !self.near_misses.is_empty() {
+            writeln!(f, "\nNear misses:")?;
+            for (evidence, reason) in self.near_misses.iter().take(MAX_NEAR_MISSES) {
+                writeln!(f, "  {evidence} ({reason})")?;
+            }
+            if self.near_misses.len() > MAX_NEAR_MISSES {
+                writeln!(
+                    f,
+                    "  ... and {} more",
+                    self.near_misses.len() - MAX_NEAR_MISSES
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Explain `package`, given every import found in the project and its dependencies, and the
+/// `report` of checking every group of the project without ignoring anything.
+pub(crate) fn explain(
+    project: &Project,
+    package: &str,
+    ignored: bool,
+    imports: &[Import],
+    report: &Report,
+) -> Result<Explanation> {
+    let name = NormalizedName::new(package);
+    let (deps, main) = if project.main_deps.declared.contains_key(&name) {
+        (&project.main_deps, true)
+    } else if project.dev_deps.declared.contains_key(&name) {
+        (&project.dev_deps, false)
+    } else {
+        bail!("{package} is not a declared dependency");
+    };
+
+    let provides = deps.provides.get(&name).into_iter().flatten();
+    let aliases: Vec<(String, String)> = provides
+        .map(|(alias, source)| (alias.clone(), source.to_string()))
+        .collect();
+    let declared = |finding: &&Finding| {
+        !matches!(
+            finding.kind,
+            FindingKind::Missing | FindingKind::StaleIgnore
+        )
+    };
+    let finding = report
+        .findings
+        .iter()
+        .filter(declared)
+        .find(|finding| finding.normalized == name.as_str())
+        .cloned();
+    // The code of a dependency only counts if the dependency is used
+    let unused: BTreeSet<&str> = report
+        .findings
+        .iter()
+        .filter(|finding| matches!(finding.kind, FindingKind::Unused | FindingKind::Transitive))
+        .map(|finding| finding.normalized.as_str())
+        .collect();
+    let mut explanation = Explanation {
+        package: deps.declared[&name].clone(),
+        main,
+        finding,
+        ignored,
+        suppression: project
+            .manifest
+            .groups
+            .values()
+            .flatten()
+            .find(|req| req.normalized == name)
+            .and_then(|req| req.suppression.clone()),
+        name_map_entry: known_entry(name.as_str()),
+        aliases,
+        credited: Vec::new(),
+        imported_by: Vec::new(),
+        near_misses: Vec::new(),
+    };
+
+    let all_deps = [&project.main_deps, &project.dev_deps];
+    for (import, path, owner) in imports {
+        let evidence = || Evidence {
+            path: path.clone(),
+            import: import.clone(),
+            owner: owner.clone(),
+        };
+        let credited = credited_by(import, &all_deps);
+        if credited.contains(&&name) {
+            // A dependency importing itself says nothing about its use
+            if owner
+                .as_ref()
+                .is_some_and(|owner| *owner == name || unused.contains(owner.as_str()))
+            {
+                continue;
+            }
+            let target = if owner.is_none() {
+                &mut explanation.credited
+            } else {
+                &mut explanation.imported_by
+            };
+            target.push(evidence());
+        } else if owner.is_none()
+            && let Some(reason) = near_miss(import, &explanation.aliases, &credited)
+        {
+            explanation.near_misses.push((evidence(), reason));
+        }
+    }
+
+    // Files are scanned in parallel, so restore a stable order
+    let key = |e: &Evidence| (e.path.clone(), e.import.line);
+    explanation.credited.sort_by_key(key);
+    explanation.imported_by.sort_by_key(key);
+    explanation.near_misses.sort_by_key(|(e, _)| key(e));
+    Ok(explanation)
+}
+
+/// Explain why an import which did not credit a dependency nearly did, if it did.
+///
+/// This is either because another dependency provides a longer module path, because the import
+/// shares a namespace package with one of the dependency's modules, or because the names are so
+/// similar it could be a typo.
+fn near_miss(
+    import: &ImportStatement,
+    aliases: &[(String, String)],
+    credited: &[&NormalizedName],
+) -> Option<String> {
+    let candidates = import_aliases(import);
+    let normalized: BTreeSet<NormalizedName> = aliases
+        .iter()
+        .map(|(alias, _)| NormalizedName::new(alias))
+        .collect();
+    if let Some(alias) = candidates.iter().find(|c| normalized.contains(*c)) {
+        let others: Vec<&str> = credited.iter().map(|c| c.as_str()).collect();
+        return Some(format!(
+            "matches `{alias}`, but {} provides a longer module path",
+            others.join(", ")
+        ));
+    }
+
+    let top = import.package.split('.').next()?;
+    let top_normalized = candidates.last()?;
+    aliases.iter().find_map(|(alias, _)| {
+        let alias_top = alias.split('.').next().unwrap_or(alias);
+        if alias
+            .strip_prefix(top)
+            .is_some_and(|rest| rest.starts_with('.'))
+        {
+            Some(format!("shares the `{top}` namespace with `{alias}`"))
+        } else if is_similar(top_normalized, &NormalizedName::new(alias_top)) {
+            Some(format!("similar to `{alias}`"))
+        } else {
+            None
+        }
+    })
+}
+
+/// Whether two different names are close enough that one may be a typo of the other.
+fn is_similar(a: &NormalizedName, b: &NormalizedName) -> bool {
+    let (a, b) = (a.as_str(), b.as_str());
+    // Short names are only allowed a single edit
+    let max = if a.len().min(b.len()) < 5 { 1 } else { 2 };
+    a != b && a.len().min(b.len()) >= 3 && distance(a, b) <= max
+}
+
+/// The Levenshtein distance between two names.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        DepType, Dependencies, get_dependencies,
+        manifest::{MAIN_GROUP, Manifest, Requirement},
+    };
+    use pretty_assertions::assert_eq;
+
+    fn import(package: &str, module: &str, line: usize) -> ImportStatement {
+        ImportStatement {
+            package: package.to_string(),
+            module: module.to_string(),
+            line,
+        }
+    }
+
+    #[test]
+    fn test_explain() {
+        let mut manifest = Manifest::default();
+        manifest.extend(
+            MAIN_GROUP,
+            ["PyYAML", "google-cloud", "google-cloud-storage", "requests"].map(Requirement::new),
+        );
+        let project = Project {
+            main_deps: get_dependencies(&manifest, &DepType::Main)
+                .unwrap()
+                .unwrap(),
+            dev_deps: Dependencies::default(),
+            files: Vec::new(),
+            manifest,
+        };
+        let imports = [
+            (import("yaml", "safe_load", 3), "b.py", None),
+            (import("yaml", "", 1), "a.py", None),
+            (import("yml", "", 2), "a.py", None),
+            (import("google.cloud", "storage", 5), "a.py", None),
+            (import("yaml", "", 9), "site.py", Some("requests")),
+            (
+                import("yaml", "", 4),
+                "google/cloud/x.py",
+                Some("google-cloud"),
+            ),
+        ]
+        .map(|(import, path, owner)| (import, PathBuf::from(path), owner.map(NormalizedName::new)));
+        let unused = |package: &str| Finding {
+            kind: FindingKind::Unused,
+            package: package.to_string(),
+            normalized: package.to_string(),
+            group: Some(MAIN_GROUP.to_string()),
+            location: None,
+            aliases: Vec::new(),
+            required_by: Vec::new(),
+            project: None,
+            suppression: None,
+        };
+        let report = Report {
+            findings: vec![unused("google-cloud")],
+            ..Report::default()
+        };
+
+        let explanation = explain(&project, "pyyaml", false, &imports, &report).unwrap();
+        assert_eq!(explanation.finding, None);
+        assert_eq!(explanation.package, "PyYAML");
+        assert_eq!(
+            explanation.aliases,
+            [
+                ("PyYAML".to_string(), "distribution name".to_string()),
+                ("yaml".to_string(), "name map".to_string())
+            ]
+        );
+        assert_eq!(
+            explanation
+                .credited
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            ["a.py:1: import yaml", "b.py:3: from yaml import safe_load"]
+        );
+        // google-cloud is unused, so its imports do not count
+        assert_eq!(
+            explanation
+                .imported_by
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            ["site.py:9: import yaml (in requests)"]
+        );
+        assert_eq!(explanation.near_misses[0].1, "similar to `yaml`");
+
+        let explanation = explain(&project, "google-cloud", false, &[], &report).unwrap();
+        assert!(explanation.credited.is_empty());
+        assert_eq!(explanation.finding, Some(unused("google-cloud")));
+        assert!(explain(&project, "numpy", false, &[], &report).is_err());
+    }
+
+    #[test]
+    fn test_near_miss() {
+        let aliases = [("google-cloud".to_string(), String::new())];
+        let storage = NormalizedName::new("google-cloud-storage");
+        assert_eq!(
+            near_miss(&import("google.cloud", "storage", 1), &aliases, &[&storage]).as_deref(),
+            Some("matches `google-cloud`, but google-cloud-storage provides a longer module path")
+        );
+        let aliases = [("google.cloud.storage".to_string(), String::new())];
+        assert_eq!(
+            near_miss(&import("google.protobuf", "", 1), &aliases, &[]).as_deref(),
+            Some("shares the `google` namespace with `google.cloud.storage`")
+        );
+        assert_eq!(near_miss(&import("numpy", "", 1), &aliases, &[]), None);
+    }
+
+    #[test]
+    fn test_distance() {
+        assert_eq!(distance("requests", "request"), 1);
+        assert_eq!(distance("yaml", "yml"), 1);
+        assert_eq!(distance("flask", "falsk"), 2);
+        assert_eq!(distance("numpy", "pandas"), 6);
+    }
+}